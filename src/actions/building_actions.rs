@@ -4,12 +4,93 @@ use bevy::prelude::*;
 use bevy::input::mouse::MouseWheel;
 
 use crate::building::BpInfos;
+use crate::character::{CameraMode,CameraState};
+use crate::inputs::InputAction;
+use crate::props::THRUSTERS_FULL_POWER;
+
+// symmetric placement around the grid origin, in grid-local coordinates
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BuildingSymmetry {
+    #[default]
+    Off,
+    MirrorX,
+    MirrorZ,
+    Radial(u32),
+}
+
+impl BuildingSymmetry {
+    // cycle order of symmetry key
+    const MODES: [BuildingSymmetry; 7] = [
+        BuildingSymmetry::Off,
+        BuildingSymmetry::MirrorX,
+        BuildingSymmetry::MirrorZ,
+        BuildingSymmetry::Radial(2),
+        BuildingSymmetry::Radial(3),
+        BuildingSymmetry::Radial(4),
+        BuildingSymmetry::Radial(6),
+    ];
+
+    pub fn next(&self) -> Self {
+        let idx = Self::MODES.iter().position(|mode| mode == self).unwrap_or(0);
+        Self::MODES[(idx + 1) % Self::MODES.len()]
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            BuildingSymmetry::Off => "Off".to_string(),
+            BuildingSymmetry::MirrorX => "Mirror X".to_string(),
+            BuildingSymmetry::MirrorZ => "Mirror Z".to_string(),
+            BuildingSymmetry::Radial(n) => format!("Radial {}", n),
+        }
+    }
+
+    // transforms of symmetric counterparts of a block, excluding the block itself
+    pub fn counterparts(&self, local_transform: &Transform) -> Vec<Transform> {
+        let t = local_transform.translation;
+        let q = local_transform.rotation;
+        match self {
+            BuildingSymmetry::Off => vec![],
+            // reflection across plane with normal n maps rotation q to n*q*n
+            BuildingSymmetry::MirrorX => vec![Transform::from_xyz(-t.x, t.y, t.z)
+                .with_rotation(Quat::from_xyzw(q.x, -q.y, -q.z, q.w))],
+            BuildingSymmetry::MirrorZ => vec![Transform::from_xyz(t.x, t.y, -t.z)
+                .with_rotation(Quat::from_xyzw(-q.x, -q.y, q.z, q.w))],
+            BuildingSymmetry::Radial(n) => (1..*n).map(|k| {
+                let turn = Quat::from_axis_angle(Vec3::Y, 2. * PI * (k as f32) / (*n as f32));
+                Transform::from_translation(turn.mul_vec3(t)).with_rotation(turn.mul_quat(q))
+            }).collect(),
+        }
+    }
+}
+
+// tools of the hammer toolbar, held while not building
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HammerTool {
+    #[default]
+    Hammer,
+    Unbuild,
+    None,
+}
+
+impl HammerTool {
+    // toolbar order
+    pub const ALL: [HammerTool; 3] = [HammerTool::Hammer, HammerTool::Unbuild, HammerTool::None];
+
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|tool| *tool == self).unwrap_or(0)
+    }
+}
 
 #[derive(Resource)]
 pub struct BuildingActionsState {
     pub building_active: bool,
+    // selected part of the build toolbar
     pub active_index: usize,
+    pub active_tool: HammerTool,
     pub active_rotation: Quat,
+    pub symmetry: BuildingSymmetry,
+    // stage assigned to placed thrusters and decouplers
+    pub active_stage: u32,
     pub thrusters_power: f32,
 }
 impl Default for BuildingActionsState {
@@ -17,7 +98,10 @@ impl Default for BuildingActionsState {
         BuildingActionsState {
             building_active: false,
             active_index: 0,
+            active_tool: HammerTool::default(),
             active_rotation: Quat::default(),
+            symmetry: BuildingSymmetry::default(),
+            active_stage: 0,
             thrusters_power: 11.,
         }
     }
@@ -27,46 +111,49 @@ impl Default for BuildingActionsState {
 pub fn update_building_actions_state(
     mut state: ResMut<BuildingActionsState>,
     infos: Res<BpInfos>,
-    keyboard_input: Res<Input<KeyCode>>,
+    action_input: Res<Input<InputAction>>,
     mut mouse_wheel_events: EventReader<MouseWheel>,
+    camera_state: Res<CameraState>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::B) {
+    let just_pressed = |action| action_input.just_pressed(action);
+
+    if just_pressed(InputAction::BuildToggle) {
         state.building_active = !state.building_active;
     }
-    if keyboard_input.just_pressed(KeyCode::H) {
+    if just_pressed(InputAction::BuildExit) {
         state.building_active = false;
     }
 
-    let mut next_index = state.active_index as i32;
+    // scrolling down or next item key selects next item of the shown toolbar
+    let mut next_index = if state.building_active { state.active_index } else { state.active_tool.index() } as i32;
     for mwe in mouse_wheel_events.read() {
-        next_index += (mwe.y/mwe.y) as i32;
-        if next_index < 0 { next_index = (infos.toolbar_order.len() as i32) - 1};
-        if next_index >= (infos.toolbar_order.len() as i32) { next_index = 0};
+        // wheel zooms orbit camera instead
+        if camera_state.mode == CameraMode::Orbit || mwe.y == 0. {
+            continue;
+        }
+        next_index -= mwe.y.signum() as i32;
     }
-    state.active_index = next_index as usize;
+    next_index += just_pressed(InputAction::ToolbarNext) as i32 - just_pressed(InputAction::ToolbarPrev) as i32;
+    let toolbar_len = if state.building_active { infos.toolbar_order.len() } else { HammerTool::ALL.len() };
+    let mut next_index = next_index.rem_euclid((toolbar_len as i32).max(1)) as usize;
 
     // toolbar key press
-    if keyboard_input.just_pressed(KeyCode::Key1) {
-        state.active_index = 0;
-    }
-    if keyboard_input.just_pressed(KeyCode::Key2) {
-        state.active_index = 1;
-    }
-    if keyboard_input.just_pressed(KeyCode::Key3) {
-        state.active_index = 2;
-    }
-    if keyboard_input.just_pressed(KeyCode::Key4) {
-        state.active_index = 3;
+    for (index, action) in InputAction::TOOLBAR.iter().enumerate() {
+        if index < toolbar_len && just_pressed(*action) {
+            next_index = index;
+        }
     }
-    if keyboard_input.just_pressed(KeyCode::Key5) {
-        state.active_index = 4;
+    if state.building_active {
+        state.active_index = next_index;
+    } else {
+        state.active_tool = HammerTool::ALL[next_index];
     }
     
     // check thrusters toggles
-    if keyboard_input.just_pressed(KeyCode::Z) {
-        state.thrusters_power *= 1.1;
+    if just_pressed(InputAction::ThrustersUp) {
+        state.thrusters_power = (state.thrusters_power * 1.1).min(THRUSTERS_FULL_POWER);
     }
-    if keyboard_input.just_pressed(KeyCode::X) {
+    if just_pressed(InputAction::ThrustersDown) {
         if state.thrusters_power > 12. {
             state.thrusters_power *= 0.909;
         }
@@ -74,13 +161,65 @@ pub fn update_building_actions_state(
 
     // rotation toggles
     if state.building_active {
-        if keyboard_input.just_pressed(KeyCode::T) {
+        if just_pressed(InputAction::RotateY) {
             state.active_rotation = state.active_rotation.mul_quat(
                 Quat::from_axis_angle(Vec3::Y, PI/2.));
         }
-        if keyboard_input.just_pressed(KeyCode::R) {
+        if just_pressed(InputAction::RotateZ) {
             state.active_rotation = state.active_rotation.mul_quat(
                 Quat::from_axis_angle(Vec3::Z, PI/2.));
         }
+        if just_pressed(InputAction::Symmetry) {
+            state.symmetry = state.symmetry.next();
+        }
+        if just_pressed(InputAction::StageNext) {
+            state.active_stage += 1;
+        }
+        if just_pressed(InputAction::StagePrev) {
+            state.active_stage = state.active_stage.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Vec3, expected: Vec3) {
+        assert!(actual.distance(expected) < 1E-5, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn mirrors_reflect_position_and_rotation() {
+        let rotation = Quat::from_rotation_y(PI / 2.) * Quat::from_rotation_x(PI / 2.);
+        let original = Transform::from_xyz(2., 1., 3.).with_rotation(rotation);
+        for (symmetry, mirror) in [
+            (BuildingSymmetry::MirrorX, Vec3::new(-1., 1., 1.)),
+            (BuildingSymmetry::MirrorZ, Vec3::new(1., 1., -1.)),
+        ] {
+            let counterparts = symmetry.counterparts(&original);
+            assert_eq!(counterparts.len(), 1);
+            assert_near(counterparts[0].translation, original.translation * mirror);
+            // mirrored block axes are the reflected axes of the original
+            for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+                assert_near(counterparts[0].rotation.mul_vec3(axis), mirror * rotation.mul_vec3(mirror * axis));
+            }
+        }
+    }
+
+    #[test]
+    fn radial_turns_about_y() {
+        let original = Transform::from_xyz(2., 1., 0.).with_rotation(Quat::from_rotation_x(PI / 2.));
+        let counterparts = BuildingSymmetry::Radial(4).counterparts(&original);
+        assert_eq!(counterparts.len(), 3);
+        for (counterpart, translation) in counterparts.iter()
+            .zip([Vec3::new(0., 1., -2.), Vec3::new(-2., 1., 0.), Vec3::new(0., 1., 2.)]) {
+            assert_near(counterpart.translation, translation);
+        }
+        let quarter = Quat::from_rotation_y(PI / 2.);
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            assert_near(counterparts[0].rotation.mul_vec3(axis), quarter.mul_vec3(original.rotation.mul_vec3(axis)));
+        }
+        assert!(BuildingSymmetry::Off.counterparts(&original).is_empty());
     }
 }
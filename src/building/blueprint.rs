@@ -0,0 +1,214 @@
+use bevy::prelude::*;
+use bevy::utils::thiserror;
+use thiserror::Error;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize,Serialize};
+use std::collections::BTreeMap;
+
+use crate::building::{BpInfos,DiscreteVec3,Grid,GridBlock,
+    insert_bp_snaps_into,spawn_grid,spawn_grid_block};
+
+// per-block settings, keyed by setting name
+#[derive(Clone,Component,Debug,Default,PartialEq,Serialize,Deserialize)]
+pub struct BlockSettings(pub BTreeMap<String,f32>);
+
+// represents a saved vehicle, blocks in grid-local coordinates
+#[derive(Clone,Debug,Default,PartialEq,Serialize,Deserialize)]
+pub struct Blueprint {
+    pub blocks: Vec<BlueprintBlock>,
+}
+
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct BlueprintBlock {
+    pub part: String,
+    pub position: DiscreteVec3,
+    pub rotation: Quat,
+    #[serde(default)]
+    pub settings: BlockSettings,
+}
+
+/// Possible errors that can be produced when reading or spawning a [`Blueprint`]
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum BlueprintError {
+    /// An [IO](std::io) Error
+    #[error("Could not read blueprint: {0}")]
+    Io(#[from] std::io::Error),
+    /// A [RON](ron) Error while parsing
+    #[error("Could not parse RON: {0}")]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    /// A [RON](ron) Error while writing
+    #[error("Could not write RON: {0}")]
+    RonError(#[from] ron::Error),
+    /// Blueprint references a part missing from [`BpInfos`]
+    #[error("Unknown part: {0}")]
+    UnknownPart(String),
+    /// Blueprint without blocks
+    #[error("Blueprint has no blocks")]
+    Empty,
+}
+
+impl BlueprintBlock {
+    pub fn local_transform(&self) -> Transform {
+        Transform::from_translation(self.position.to_vec3()).with_rotation(self.rotation)
+    }
+}
+
+impl Blueprint {
+    // collects blocks of grid, sorted by position for stable output
+    pub fn from_grid(
+        grid_entity: Entity,
+        gb_query: &Query<(&GridBlock, Option<&BlockSettings>)>,
+    ) -> Self {
+        let mut blocks: Vec<BlueprintBlock> = gb_query.iter()
+            .filter(|(gb, _)| gb.entity == grid_entity)
+            .filter_map(|(gb, settings)| {
+                let (translation, rotation, _) = gb.collider.as_ref()?;
+                Some(BlueprintBlock {
+                    part: gb.bp_name.clone(),
+                    position: DiscreteVec3::from(*translation),
+                    rotation: rotation.normalize(),
+                    settings: settings.cloned().unwrap_or_default(),
+                })
+            })
+            .collect();
+        blocks.sort_by_key(|block| block.position.0);
+        Blueprint { blocks }
+    }
+
+    pub fn from_ron_str(ron_str: &str) -> Result<Self, BlueprintError> {
+        Ok(ron::de::from_str::<Blueprint>(ron_str)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, BlueprintError> {
+        Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self, BlueprintError> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<(), BlueprintError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_ron_string()?)?;
+        Ok(())
+    }
+
+    // spawns blueprint as a new grid at transform, with collider and snap points
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        infos: &BpInfos,
+        transform: Transform,
+    ) -> Result<Entity, BlueprintError> {
+        if self.blocks.is_empty() {
+            return Err(BlueprintError::Empty);
+        }
+        if let Some(block) = self.blocks.iter().find(|block| !infos.bps.contains_key(&block.part)) {
+            return Err(BlueprintError::UnknownPart(block.part.clone()));
+        }
+
+        let grid_entity = spawn_grid(commands, transform);
+        let mut grid = Grid::default();
+        let mut colliders = vec![];
+        for block in self.blocks.iter() {
+            let bp_info = &infos.bps[&block.part];
+            let local_transform = block.local_transform();
+            let settings = (!block.settings.0.is_empty()).then(|| block.settings.clone());
+            spawn_grid_block(commands, grid_entity, &block.part, bp_info, local_transform, settings);
+            insert_bp_snaps_into(commands, bp_info, grid_entity, &mut grid, &local_transform);
+            colliders.push((local_transform.translation, local_transform.rotation, bp_info.collider.clone()));
+        }
+        commands.entity(grid_entity)
+            .insert(grid)
+            .insert(Collider::compound(colliders));
+        Ok(grid_entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::building::{BpInfo,BpSnapPoint};
+    use bevy::ecs::system::SystemState;
+
+    fn cube_infos() -> BpInfos {
+        let snap = [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z].iter()
+            .map(|normal| BpSnapPoint { point: 0.5 * *normal, normal: *normal, ..default() })
+            .collect();
+        let cube = BpInfo {
+            mass: 1.,
+            collider: Collider::cuboid(0.5, 0.5, 0.5),
+            snap,
+            ..default()
+        };
+        BpInfos {
+            bps: [("cube".to_string(), cube)].into_iter().collect(),
+            toolbar_order: vec!["cube".into()],
+        }
+    }
+
+    fn spawn(app: &mut App, blueprint: &Blueprint, infos: &BpInfos) -> Entity {
+        let mut state = SystemState::<Commands>::new(&mut app.world);
+        let grid_entity = blueprint.spawn(&mut state.get_mut(&mut app.world), infos, Transform::IDENTITY).unwrap();
+        state.apply(&mut app.world);
+        app.update();
+        grid_entity
+    }
+
+    fn from_grid(app: &mut App, grid_entity: Entity) -> Blueprint {
+        let mut state = SystemState::<Query<(&GridBlock, Option<&BlockSettings>)>>::new(&mut app.world);
+        Blueprint::from_grid(grid_entity, &state.get(&app.world))
+    }
+
+    fn grid_spaces(app: &App, grid_entity: Entity) -> (Vec<DiscreteVec3>, Vec<(DiscreteVec3, usize)>) {
+        let grid = app.world.get::<Grid>(grid_entity).unwrap();
+        let mut solid: Vec<DiscreteVec3> = grid.solid_blocks.keys().cloned().collect();
+        solid.sort_by_key(|pos| pos.0);
+        let mut snaps: Vec<(DiscreteVec3, usize)> = grid.snaps_for_space.iter()
+            .map(|(pos, snaps)| (pos.clone(), snaps.len())).collect();
+        snaps.sort_by_key(|(pos, _)| pos.0);
+        (solid, snaps)
+    }
+
+    #[test]
+    fn round_trip_keeps_grid() {
+        let infos = cube_infos();
+        let blueprint = Blueprint {
+            blocks: [[0, 0, 0], [1, 0, 0], [1, 1, 0]].into_iter().map(|position| BlueprintBlock {
+                part: "cube".into(),
+                position: DiscreteVec3(position),
+                rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+                settings: BlockSettings([("stage".to_string(), 1.)].into_iter().collect()),
+            }).collect(),
+        };
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins);
+        let original = spawn(&mut app, &blueprint, &infos);
+        let saved = from_grid(&mut app, original);
+        let loaded = Blueprint::from_ron_str(&saved.to_ron_string().unwrap()).unwrap();
+        assert_eq!(loaded.blocks.len(), 3);
+        assert_eq!(loaded.blocks.iter().map(|b| &b.settings).collect::<Vec<_>>(),
+            saved.blocks.iter().map(|b| &b.settings).collect::<Vec<_>>());
+        let respawned = spawn(&mut app, &loaded, &infos);
+
+        let (solid, snaps) = grid_spaces(&app, original);
+        assert_eq!(solid.len(), 3);
+        assert!(!snaps.is_empty());
+        assert_eq!(grid_spaces(&app, respawned), (solid, snaps));
+        assert_eq!(from_grid(&mut app, respawned).blocks.iter().map(|b| &b.position).collect::<Vec<_>>(),
+            saved.blocks.iter().map(|b| &b.position).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unknown_part_is_rejected() {
+        let blueprint = Blueprint::from_ron_str("(blocks: [(part: \"missing\", position: ((0, 0, 0)), rotation: (0.0, 0.0, 0.0, 1.0))])").unwrap();
+        let mut app = App::new();
+        let mut state = SystemState::<Commands>::new(&mut app.world);
+        let spawned = blueprint.spawn(&mut state.get_mut(&mut app.world), &cube_infos(), Transform::IDENTITY);
+        assert!(matches!(spawned, Err(BlueprintError::UnknownPart(part)) if part == "missing"));
+    }
+}
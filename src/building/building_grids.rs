@@ -1,18 +1,25 @@
 use bevy::{prelude::*, utils::HashMap};
 use bevy_rapier3d::prelude::*;
+use serde::{Deserialize,Serialize};
+use std::collections::VecDeque;
+use crate::building::{BlockSettings,BlueprintBlock,BpInfo,BpInfos,BpSnapsEvent};
 use crate::character::{CharacterFpsMotionConfig,CHARACTER_GROUP};
+use crate::props::{block_stage,Decoupler,DECOUPLER_PART,FuelTank,InteractableInfo,INTERACT_GROUP,ThrusterInteractable};
 use crate::world::WORLD_GROUP;
 
 
-#[derive(Default,Clone)]
+// max distance from a whole block position to count as aligned with a grid
+const GRID_ALIGN_EPSILON: f32 = 0.1;
+
+#[derive(Default,Clone,Deserialize)]
 pub enum GridSolidity {
     #[default]
     Solid,
     Leaky,
 }
 
-#[derive(Default,Debug,Clone,PartialEq,Eq,Hash)]
-pub struct DiscreteVec3([i32; 3]);
+#[derive(Default,Debug,Clone,PartialEq,Eq,Hash,Serialize,Deserialize)]
+pub struct DiscreteVec3(pub [i32; 3]);
 impl From<Vec3> for DiscreteVec3 {
     fn from(v: Vec3) -> Self {
         DiscreteVec3([
@@ -22,6 +29,24 @@ impl From<Vec3> for DiscreteVec3 {
         ])
     }
 }
+impl DiscreteVec3 {
+    pub fn to_vec3(&self) -> Vec3 {
+        Vec3::new(self.0[0] as f32, self.0[1] as f32, self.0[2] as f32)
+    }
+
+    // the six face-adjacent positions
+    pub fn neighbours(&self) -> [DiscreteVec3; 6] {
+        let [x, y, z] = self.0;
+        [
+            DiscreteVec3([x + 1, y, z]),
+            DiscreteVec3([x - 1, y, z]),
+            DiscreteVec3([x, y + 1, z]),
+            DiscreteVec3([x, y - 1, z]),
+            DiscreteVec3([x, y, z + 1]),
+            DiscreteVec3([x, y, z - 1]),
+        ]
+    }
+}
 
 #[derive(Component,Default,Clone)]
 pub struct Grid {
@@ -32,6 +57,7 @@ pub struct Grid {
 #[derive(Component,Debug)]
 pub struct GridBlock {
     pub entity: Entity,
+    pub bp_name: String,
     pub collider: Option<(Vec3, Quat, Collider)>,
 }
 
@@ -44,16 +70,570 @@ pub fn find_or_create_grid(
     if grid_ent.is_some() { 
         (grid_ent.unwrap(), *transforms_query.get(grid_ent.unwrap()).unwrap().0)
     } else {
-        (commands.spawn(SpatialBundle {
-                transform: target_transform,
+        (spawn_grid(commands, target_transform), target_transform)
+    }
+}
+
+pub fn spawn_grid(
+    commands: &mut Commands,
+    target_transform: Transform,
+) -> Entity {
+    commands.spawn(SpatialBundle {
+            transform: target_transform,
+            ..default()
+        })
+        .insert(Grid::default())
+        .insert(RigidBody::Dynamic)
+        .insert(Velocity::default())
+        .insert(ExternalForce { ..default() })
+        .insert(ExternalImpulse::default())
+        .insert(ReadMassProperties::default())
+        .insert(CollisionGroups::new(CHARACTER_GROUP | WORLD_GROUP, CHARACTER_GROUP | WORLD_GROUP))
+        .id()
+}
+
+// spawns block entity in grid, without gltf scene or grid collider/snaps updates
+pub fn spawn_grid_block(
+    commands: &mut Commands,
+    grid_entity: Entity,
+    bp_name: &str,
+    bp_info: &BpInfo,
+    local_transform: Transform,
+    settings: Option<BlockSettings>,
+) -> Entity {
+    let local_translation = local_transform.translation;
+    let rot_quat = local_transform.rotation;
+    let mut build_block = commands.spawn(SpatialBundle {
+        transform: local_transform,
+        ..default()
+    });
+    build_block.insert(AdditionalMassProperties::Mass(bp_info.mass));
+    build_block.insert(GridBlock { 
+        entity: grid_entity, 
+        bp_name: bp_name.to_string(),
+        collider: Some((local_translation, rot_quat, bp_info.collider.clone())), 
+    });
+    build_block.set_parent(grid_entity);
+    if bp_info.fuel_capacity > 0. {
+        // tanks spawn full unless fuel level was saved
+        let fuel = settings.as_ref().and_then(|s| s.0.get("fuel")).copied()
+            .unwrap_or(bp_info.fuel_capacity);
+        build_block.insert(FuelTank { fuel: fuel.clamp(0., bp_info.fuel_capacity), capacity: bp_info.fuel_capacity });
+    }
+    // add block interactable extras
+    if bp_name == "flight_seat" {
+        build_block.with_children(|parent| {
+            parent.spawn(SpatialBundle {
+                transform: Transform::from_translation(local_translation).with_rotation(rot_quat),
                 ..default()
             })
-            .insert(Grid::default())
-            .insert(RigidBody::Dynamic)
-            .insert(ExternalForce { ..default() })
-            .insert(CollisionGroups::new(CHARACTER_GROUP | WORLD_GROUP, CHARACTER_GROUP | WORLD_GROUP))
-            .id(),
-            target_transform,
-        )
+                .insert(InteractableInfo { hover_text: "Enter Flight Seat (F)".into() })
+                .insert(bp_info.collider.clone())
+                .insert(CollisionGroups::new(INTERACT_GROUP, INTERACT_GROUP));
+        });
+    }
+    if bp_name == "thruster" {
+        let stage = block_stage(settings.as_ref());
+        build_block.insert(ThrusterInteractable { stage, active: stage == 0 });
+    }
+    if bp_name == DECOUPLER_PART {
+        build_block.insert(Decoupler { stage: block_stage(settings.as_ref()) });
+    }
+    if let Some(settings) = settings {
+        build_block.insert(settings);
+    }
+    build_block.id()
+}
+
+// spawns blocks into existing grid, inserting their snaps and rebuilding the grid collider.
+// other grids bridged by the new blocks are merged into the grid, as are restored grids
+// along with their transform into the grid's frame, regardless of alignment
+pub fn place_grid_blocks(
+    commands: &mut Commands,
+    grid_entity: Entity,
+    grid_transform: &Transform,
+    blocks: &[BlueprintBlock],
+    infos: &BpInfos,
+    grids_query: &Query<(Entity, &GlobalTransform, &Grid, Option<&Velocity>, Option<&ReadMassProperties>)>,
+    gb_query: &Query<(Entity, &GridBlock)>,
+    snaps_events: &mut EventWriter<BpSnapsEvent>,
+    restored_grids: &[(Entity, Transform)],
+) -> (Vec<Entity>, Vec<GridMerge>) {
+    let mut colliders = grid_block_colliders(gb_query, grid_entity, None);
+    let mut block_entities = vec![];
+    let mut adjacent_grids: Vec<Entity> = vec![];
+    for block in blocks.iter() {
+        let Some(bp_info) = infos.bps.get(&block.part) else {
+            continue;
+        };
+        let local_transform = block.local_transform();
+        let settings = (!block.settings.0.is_empty()).then(|| block.settings.clone());
+        block_entities.push(spawn_grid_block(commands, grid_entity, &block.part, bp_info, local_transform, settings));
+
+        // insert building tool snap colliders
+        snaps_events.send(BpSnapsEvent::InsertSnaps(Box::new(bp_info.clone()), grid_entity, local_transform));
+        colliders.push((local_transform.translation, local_transform.rotation, bp_info.collider.clone()));
+
+        // other grids bridged by new block
+        let block_world_pos = grid_transform.transform_point(local_transform.translation);
+        for adjacent_grid in find_adjacent_grids(grid_entity, grid_transform.rotation, block_world_pos, grids_query) {
+            if !adjacent_grids.contains(&adjacent_grid) {
+                adjacent_grids.push(adjacent_grid);
+            }
+        }
+    }
+
+    let mut absorbed_grids = restored_grids.to_vec();
+    for adjacent_grid in adjacent_grids {
+        if absorbed_grids.iter().any(|(grid, _)| *grid == adjacent_grid) {
+            continue;
+        }
+        let (_, adjacent_gt, ..) = grids_query.get(adjacent_grid).unwrap();
+        absorbed_grids.push((adjacent_grid, Transform::from_matrix(
+            grid_transform.compute_matrix().inverse() * adjacent_gt.compute_matrix())));
+    }
+
+    // merge bridged grids, the merged body keeps their combined momentum
+    let mut merges = vec![];
+    let mut momentum = grid_momentum(grids_query.get(grid_entity).ok().map(|(_, _, _, v, mp)| (v, mp)));
+    for (absorbed_grid, to_target) in absorbed_grids {
+        let Ok((_, _, _, velocity, mass_props)) = grids_query.get(absorbed_grid) else {
+            continue;
+        };
+        let (mass, velocity) = grid_momentum(Some((velocity, mass_props)));
+        momentum = (momentum.0 + mass, Velocity {
+            linvel: momentum.1.linvel + velocity.linvel,
+            angvel: momentum.1.angvel + velocity.angvel,
+        });
+        let merge = GridMerge {
+            absorbed: absorbed_grid,
+            target: grid_entity,
+            to_target,
+            blocks: gb_query.iter().filter(|(_, gb)| gb.entity == absorbed_grid).map(|(block, _)| block).collect(),
+        };
+        colliders.extend(merge_grid_into(commands, &merge, infos, gb_query, snaps_events));
+        merges.push(merge);
+    }
+    if !merges.is_empty() {
+        let (mass, weighted) = momentum;
+        commands.entity(grid_entity).insert(Velocity {
+            linvel: weighted.linvel / mass,
+            angvel: weighted.angvel / mass,
+        });
+    }
+
+    commands.entity(grid_entity).insert(Collider::compound(
+        colliders
+    ));
+    (block_entities, merges)
+}
+
+// mass and mass-weighted velocity of a grid, grids without computed mass weigh next to nothing.
+// angular velocity is weighted by mass too, ignoring the grids' inertia
+fn grid_momentum(body: Option<(Option<&Velocity>, Option<&ReadMassProperties>)>) -> (f32, Velocity) {
+    let (velocity, mass_props) = body.unwrap_or_default();
+    let mass = mass_props.map_or(0., |mp| mp.mass).max(1E-3);
+    let velocity = velocity.copied().unwrap_or_default();
+    (mass, Velocity { linvel: velocity.linvel * mass, angvel: velocity.angvel * mass })
+}
+
+// finds block of grid at grid-local position
+pub fn find_grid_block(
+    gb_query: &Query<(Entity, &GridBlock)>,
+    grid_entity: Entity,
+    block_pos: &DiscreteVec3,
+) -> Option<Entity> {
+    gb_query.iter()
+        .find(|(_, gb)| {
+            gb.entity == grid_entity &&
+                gb.collider.as_ref().is_some_and(|c| DiscreteVec3::from(c.0) == *block_pos)
+        })
+        .map(|(block_entity, _)| block_entity)
+}
+
+// groups positions into face-connected islands, returned as indices into positions
+pub fn grid_islands(positions: &[DiscreteVec3]) -> Vec<Vec<usize>> {
+    let index_of: HashMap<&DiscreteVec3,usize> = positions.iter().enumerate()
+        .map(|(idx, pos)| (pos, idx)).collect();
+    let mut visited = vec![false; positions.len()];
+    let mut islands = vec![];
+
+    for start in 0..positions.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut island = vec![];
+        let mut queue = VecDeque::from([start]);
+        while let Some(idx) = queue.pop_front() {
+            island.push(idx);
+            for neighbour in positions[idx].neighbours().iter() {
+                if let Some(&nidx) = index_of.get(neighbour) {
+                    if !visited[nidx] {
+                        visited[nidx] = true;
+                        queue.push_back(nidx);
+                    }
+                }
+            }
+        }
+        islands.push(island);
+    }
+    islands
+}
+
+// rotation turning each axis onto another axis, as between grids that can merge
+pub fn is_right_angle_rotation(rotation: Quat) -> bool {
+    [Vec3::X, Vec3::Y, Vec3::Z].iter()
+        .all(|axis| rotation.mul_vec3(*axis).abs().max_element() > 1. - GRID_ALIGN_EPSILON * GRID_ALIGN_EPSILON)
+}
+
+// right angle rotation nearest to rotation, removing drift from merged block rotations
+pub fn snap_right_angle(rotation: Quat) -> Quat {
+    let [x, y, _] = [Vec3::X, Vec3::Y, Vec3::Z].map(|axis| {
+        let v = rotation.mul_vec3(axis);
+        let a = v.abs();
+        if a.x >= a.y && a.x >= a.z { Vec3::X * v.x.signum() }
+        else if a.y >= a.z { Vec3::Y * v.y.signum() }
+        else { Vec3::Z * v.z.signum() }
+    });
+    Quat::from_mat3(&Mat3::from_cols(x, y, x.cross(y)))
+}
+
+// finds other grids with snap points facing the given world position,
+// rotated by right angles against the grid so their blocks stay on its cells
+pub fn find_adjacent_grids(
+    grid_entity: Entity,
+    grid_rotation: Quat,
+    block_world_pos: Vec3,
+    grids_query: &Query<(Entity, &GlobalTransform, &Grid, Option<&Velocity>, Option<&ReadMassProperties>)>,
+) -> Vec<Entity> {
+    grids_query.iter()
+        .filter(|(other_entity, other_gt, other_grid, _, _)| {
+            if *other_entity == grid_entity {
+                return false;
+            }
+            let (_, other_rotation, _) = other_gt.to_scale_rotation_translation();
+            if !is_right_angle_rotation(other_rotation.inverse() * grid_rotation) {
+                return false;
+            }
+            // only aligned with other grid's block positions
+            let other_local = other_gt.affine().inverse().transform_point3(block_world_pos);
+            if (other_local - other_local.round()).length() > GRID_ALIGN_EPSILON {
+                return false;
+            }
+            let other_pos = DiscreteVec3::from(other_local);
+            other_grid.snaps_for_space.contains_key(&other_pos) &&
+                !other_grid.solid_blocks.contains_key(&other_pos)
+        })
+        .map(|(other_entity, ..)| other_entity)
+        .collect()
+}
+
+// grid absorbed into another, with transform from its local frame into the target's
+// and the blocks it brought along
+#[derive(Clone, Debug)]
+pub struct GridMerge {
+    pub absorbed: Entity,
+    pub target: Entity,
+    pub to_target: Transform,
+    pub blocks: Vec<Entity>,
+}
+
+// moves all blocks of absorbed grid into target grid, re-expressed in target
+// grid's local frame, and despawns absorbed grid. returns moved block colliders
+pub fn merge_grid_into(
+    commands: &mut Commands,
+    merge: &GridMerge,
+    infos: &BpInfos,
+    gb_query: &Query<(Entity, &GridBlock)>,
+    snaps_events: &mut EventWriter<BpSnapsEvent>,
+) -> Vec<(Vec3,Quat,Collider)> {
+    let (target_grid, absorbed_grid, to_target) = (merge.target, merge.absorbed, merge.to_target);
+    let mut colliders = vec![];
+    for (block_entity, gb) in gb_query.iter_many(&merge.blocks) {
+        let Some((translation, rotation, collider)) = gb.collider.clone() else {
+            continue;
+        };
+        let block_transform = to_target.mul_transform(
+            Transform::from_translation(translation).with_rotation(rotation));
+        let translation = DiscreteVec3::from(block_transform.translation).to_vec3();
+        let rotation = snap_right_angle(block_transform.rotation);
+
+        commands.entity(block_entity)
+            .insert(Transform::from_translation(translation).with_rotation(rotation))
+            .insert(GridBlock {
+                entity: target_grid,
+                bp_name: gb.bp_name.clone(),
+                collider: Some((translation, rotation, collider.clone())),
+            })
+            .set_parent(target_grid);
+        if let Some(bp_info) = infos.bps.get(&gb.bp_name) {
+            snaps_events.send(BpSnapsEvent::InsertSnaps(
+                Box::new(bp_info.clone()), target_grid, Transform::from_translation(translation).with_rotation(rotation)));
+        }
+        colliders.push((translation, rotation, collider));
+    }
+
+    // blocks are re-parented first, remaining children are old snaps
+    commands.entity(absorbed_grid).despawn_recursive();
+    colliders
+}
+
+// reverses a merge, moving its blocks still in the target grid back into a new grid
+// at their frame before the merge, moving with the target. returns the restored grid.
+// target grid collider and snaps are left to detach_grid_blocks
+pub fn unmerge_grid(
+    commands: &mut Commands,
+    merge: &GridMerge,
+    infos: &BpInfos,
+    grids_query: &Query<(&Transform, Option<&Velocity>, Option<&ReadMassProperties>), With<Grid>>,
+    gb_query: &Query<(Entity, &GridBlock)>,
+    snaps_events: &mut EventWriter<BpSnapsEvent>,
+) -> Entity {
+    let (target_transform, target_velocity, target_com) = grids_query.get(merge.target)
+        .map(|(t, v, mp)| (*t, v.copied().unwrap_or_default(), mp.map_or(Vec3::ZERO, |mp| mp.local_center_of_mass)))
+        .unwrap_or_default();
+    let from_target = Transform::from_matrix(merge.to_target.compute_matrix().inverse());
+    let restored_grid = spawn_grid(commands, target_transform.mul_transform(merge.to_target));
+    let moved: Vec<(Entity, &GridBlock)> = gb_query.iter_many(&merge.blocks)
+        .filter(|(_, gb)| gb.entity == merge.target)
+        .collect();
+    let moved_com = island_center_of_mass(moved.iter().map(|(_, gb)| *gb), infos);
+    commands.entity(restored_grid).insert(point_velocity(
+        &target_velocity, target_transform.rotation.mul_vec3(moved_com - target_com)));
+
+    let mut colliders = vec![];
+    for (block_entity, gb) in moved {
+        let Some((translation, rotation, collider)) = gb.collider.clone() else {
+            continue;
+        };
+        let block_transform = from_target.mul_transform(
+            Transform::from_translation(translation).with_rotation(rotation));
+        let translation = DiscreteVec3::from(block_transform.translation).to_vec3();
+        let rotation = snap_right_angle(block_transform.rotation);
+
+        commands.entity(block_entity)
+            .insert(Transform::from_translation(translation).with_rotation(rotation))
+            .insert(GridBlock {
+                entity: restored_grid,
+                bp_name: gb.bp_name.clone(),
+                collider: Some((translation, rotation, collider.clone())),
+            })
+            .set_parent(restored_grid);
+        if let Some(bp_info) = infos.bps.get(&gb.bp_name) {
+            snaps_events.send(BpSnapsEvent::InsertSnaps(
+                Box::new(bp_info.clone()), restored_grid, Transform::from_translation(translation).with_rotation(rotation)));
+        }
+        colliders.push((translation, rotation, collider));
+    }
+    commands.entity(restored_grid).insert(Collider::compound(colliders));
+    restored_grid
+}
+
+// collects compound collider parts of all blocks in grid, skipping excluded block
+pub fn grid_block_colliders(
+    gb_query: &Query<(Entity, &GridBlock)>,
+    grid_entity: Entity,
+    exclude: Option<Entity>,
+) -> Vec<(Vec3,Quat,Collider)> {
+    gb_query.iter()
+        .filter(|(ent, gb)| { gb.entity == grid_entity && gb.collider.is_some() && Some(*ent) != exclude })
+        .map(|(_, gb)| { gb.collider.clone().unwrap() }).collect()
+}
+
+// grid-local center of mass of blocks, weighted by part mass
+pub fn island_center_of_mass<'a>(blocks: impl Iterator<Item = &'a GridBlock>, infos: &BpInfos) -> Vec3 {
+    let (weighted, total) = blocks.fold((Vec3::ZERO, 0.), |(weighted, total), gb| {
+        let mass = infos.bps.get(&gb.bp_name).map_or(1., |bp_info| bp_info.mass).max(1E-3);
+        (weighted + mass * gb.collider.as_ref().map_or(Vec3::ZERO, |c| c.0), total + mass)
+    });
+    if total > 0. { weighted / total } else { Vec3::ZERO }
+}
+
+// velocity of a body at offset from its center of mass, in world space
+pub fn point_velocity(velocity: &Velocity, offset: Vec3) -> Velocity {
+    Velocity {
+        linvel: velocity.linvel + velocity.angvel.cross(offset),
+        angvel: velocity.angvel,
+    }
+}
+
+// despawns blocks from grid, rebuilding grid collider and snaps,
+// or despawning the grid if no blocks remain.
+// blocks no longer connected to the largest remaining island
+// are split off into new grids, which are returned
+pub fn remove_grid_blocks(
+    commands: &mut Commands,
+    grid_entity: Entity,
+    block_entities: &[Entity],
+    infos: &BpInfos,
+    grids_query: &Query<(&Transform, Option<&Velocity>, Option<&ReadMassProperties>), With<Grid>>,
+    gb_query: &Query<(Entity, &GridBlock)>,
+    snaps_events: &mut EventWriter<BpSnapsEvent>,
+) -> Vec<Entity> {
+    for (block_entity, _) in gb_query.iter_many(block_entities) {
+        commands.entity(block_entity).despawn_recursive();
+    }
+    detach_grid_blocks(commands, grid_entity, block_entities, infos, grids_query, gb_query, snaps_events)
+}
+
+// rebuilds grid collider and snaps without blocks that were despawned or moved to
+// another grid, despawning the grid if no blocks remain. blocks no longer connected
+// to the largest remaining island are split off into new grids, which are returned
+pub fn detach_grid_blocks(
+    commands: &mut Commands,
+    grid_entity: Entity,
+    block_entities: &[Entity],
+    infos: &BpInfos,
+    grids_query: &Query<(&Transform, Option<&Velocity>, Option<&ReadMassProperties>), With<Grid>>,
+    gb_query: &Query<(Entity, &GridBlock)>,
+    snaps_events: &mut EventWriter<BpSnapsEvent>,
+) -> Vec<Entity> {
+    let mut freed_positions: Vec<DiscreteVec3> = gb_query.iter_many(block_entities)
+        .filter(|(_, gb)| gb.entity == grid_entity)
+        .map(|(_, gb)| DiscreteVec3::from(gb.collider.as_ref().map_or(Vec3::ZERO, |c| c.0)))
+        .collect();
+    if freed_positions.is_empty() {
+        return vec![];
+    }
+
+    let remaining: Vec<(Entity, &GridBlock)> = gb_query.iter()
+        .filter(|(ent, gb)| { gb.entity == grid_entity && gb.collider.is_some() && !block_entities.contains(ent) })
+        .collect();
+    if remaining.is_empty() {
+        commands.entity(grid_entity).despawn_recursive();
+        return vec![];
+    }
+
+    // connectivity pass, largest island stays in this grid
+    let positions: Vec<DiscreteVec3> = remaining.iter()
+        .map(|(_, gb)| DiscreteVec3::from(gb.collider.as_ref().unwrap().0)).collect();
+    let mut islands = grid_islands(&positions);
+    islands.sort_by_key(|island| std::cmp::Reverse(island.len()));
+
+    let (grid_transform, grid_velocity, grid_com) = grids_query.get(grid_entity)
+        .map(|(t, v, mp)| (*t, v.copied().unwrap_or_default(), mp.map_or(Vec3::ZERO, |mp| mp.local_center_of_mass)))
+        .unwrap_or_default();
+    let mut split_grids = vec![];
+    for island in islands.iter().skip(1) {
+        let split_grid = spawn_grid(commands, grid_transform);
+        split_grids.push(split_grid);
+        // split islands keep moving with the point of the spinning grid they were
+        let island_com = island_center_of_mass(island.iter().map(|&idx| remaining[idx].1), infos);
+        commands.entity(split_grid).insert(point_velocity(
+            &grid_velocity, grid_transform.rotation.mul_vec3(island_com - grid_com)));
+
+        let mut colliders = vec![];
+        for &idx in island.iter() {
+            let (split_block, gb) = remaining[idx];
+            let (translation, rotation, collider) = gb.collider.clone().unwrap();
+            commands.entity(split_block)
+                .insert(GridBlock {
+                    entity: split_grid,
+                    bp_name: gb.bp_name.clone(),
+                    collider: gb.collider.clone(),
+                })
+                .set_parent(split_grid);
+            if let Some(bp_info) = infos.bps.get(&gb.bp_name) {
+                snaps_events.send(BpSnapsEvent::InsertSnaps(
+                    Box::new(bp_info.clone()), split_grid, Transform::from_translation(translation).with_rotation(rotation)));
+            }
+            colliders.push((translation, rotation, collider));
+            freed_positions.push(positions[idx].clone());
+        }
+        commands.entity(split_grid).insert(Collider::compound(colliders));
+    }
+
+    let colliders = islands[0].iter()
+        .map(|&idx| remaining[idx].1.collider.clone().unwrap()).collect();
+    commands.entity(grid_entity).insert(Collider::compound(colliders));
+
+    // remove snaps of removed and split blocks, regenerate neighbouring snaps
+    snaps_events.send(BpSnapsEvent::RemoveSnaps(grid_entity, freed_positions));
+    split_grids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn right_angle_rotations_merge() {
+        let quarter = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+        assert!(is_right_angle_rotation(Quat::IDENTITY));
+        assert!(is_right_angle_rotation(quarter * Quat::from_rotation_x(std::f32::consts::PI)));
+        assert!(!is_right_angle_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_4)));
+        assert!(!is_right_angle_rotation(Quat::from_rotation_z(0.5)));
+    }
+
+    #[test]
+    fn snapped_rotation_is_exact() {
+        let quarter = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+        let snapped = snap_right_angle(quarter * Quat::from_rotation_x(0.02));
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            assert!(snapped.mul_vec3(axis).distance(quarter.mul_vec3(axis)) < 1E-6);
+        }
+    }
+
+    #[test]
+    fn grids_without_mass_weigh_equally() {
+        let moving = Velocity { linvel: Vec3::new(2., 0., 0.), angvel: Vec3::Y };
+        let mass_props = ReadMassProperties::default();
+        let (moving_mass, moving_momentum) = grid_momentum(Some((Some(&moving), Some(&mass_props))));
+        let (new_mass, new_momentum) = grid_momentum(None);
+        assert_eq!(moving_mass, new_mass);
+        let merged = (moving_momentum.linvel + new_momentum.linvel) / (moving_mass + new_mass);
+        assert!(merged.distance(Vec3::X) < 1E-6);
+    }
+
+    #[test]
+    fn point_velocity_adds_spin() {
+        let velocity = Velocity { linvel: Vec3::new(1., 0., 0.), angvel: Vec3::new(0., 0., 2.) };
+        // (0, 0, 2) x (0, 3, 0) = (-6, 0, 0)
+        let at_point = point_velocity(&velocity, Vec3::new(0., 3., 0.));
+        assert_eq!(at_point.linvel, Vec3::new(-5., 0., 0.));
+        assert_eq!(at_point.angvel, velocity.angvel);
+    }
+
+    #[test]
+    fn split_islands_move_with_spinning_grid() {
+        use bevy::ecs::system::SystemState;
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_event::<BpSnapsEvent>();
+        let infos = BpInfos {
+            bps: [("cube".to_string(), BpInfo { mass: 1., collider: Collider::cuboid(0.5, 0.5, 0.5), ..default() })].into(),
+            toolbar_order: vec!["cube".into()],
+        };
+        // quarter turn about y, grid x axis points along world -z
+        let grid_transform = Transform::from_xyz(5., 0., 0.).with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2));
+        let grid = app.world.spawn((grid_transform, Grid::default(), ReadMassProperties::default(),
+            Velocity { linvel: Vec3::X, angvel: Vec3::Y })).id();
+        let blocks: Vec<Entity> = (-2..=2).map(|x| app.world.spawn(GridBlock {
+            entity: grid,
+            bp_name: "cube".into(),
+            collider: Some((Vec3::new(x as f32, 0., 0.), Quat::IDENTITY, Collider::cuboid(0.5, 0.5, 0.5))),
+        }).id()).collect();
+
+        // removing x = 1 splits off the block at x = 2
+        let mut state = SystemState::<(
+            Commands,
+            Query<(&Transform, Option<&Velocity>, Option<&ReadMassProperties>), With<Grid>>,
+            Query<(Entity, &GridBlock)>,
+            EventWriter<BpSnapsEvent>,
+        )>::new(&mut app.world);
+        let (mut commands, grids_query, gb_query, mut snaps_events) = state.get_mut(&mut app.world);
+        remove_grid_blocks(&mut commands, grid, &[blocks[3]], &infos, &grids_query, &gb_query, &mut snaps_events);
+        state.apply(&mut app.world);
+
+        let split_grid = app.world.get::<GridBlock>(blocks[4]).unwrap().entity;
+        assert_ne!(split_grid, grid);
+        assert_eq!(app.world.get::<GridBlock>(blocks[0]).unwrap().entity, grid);
+        // island center (2, 0, 0) is (0, 0, -2) from the grid center in world space,
+        // y x (0, 0, -2) = (-2, 0, 0)
+        let split_velocity = app.world.get::<Velocity>(split_grid).unwrap();
+        assert!(split_velocity.linvel.distance(Vec3::new(-1., 0., 0.)) < 1E-5, "{}", split_velocity.linvel);
+        assert_eq!(split_velocity.angvel, Vec3::Y);
+        assert_eq!(app.world.get::<Velocity>(grid).unwrap().linvel, Vec3::X);
     }
 }
@@ -0,0 +1,446 @@
+use crate::building::{BlueprintBlock,BpInfos,BpSnapsEvent,DiscreteVec3,Grid,GridBlock,GridMerge,
+    detach_grid_blocks,place_grid_blocks,snap_right_angle,spawn_grid,unmerge_grid};
+use crate::game_state::GameState;
+use crate::inputs::KeyInputState;
+use crate::world::OriginShiftEvent;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_rapier3d::prelude::*;
+
+// maximum number of undoable building actions kept
+const HISTORY_LIMIT: usize = 100;
+
+// reversible building action, blocks in grid-local coordinates along with their entities.
+// placements keep the grids they merged, removals the grids they split off,
+// both with transforms into the recorded grid's frame
+#[derive(Clone, Debug)]
+pub enum BuildingAction {
+    Place {
+        grid: Entity,
+        grid_transform: Transform,
+        blocks: Vec<BlueprintBlock>,
+        entities: Vec<Entity>,
+        merges: Vec<GridMerge>,
+    },
+    Remove {
+        grid: Entity,
+        grid_transform: Transform,
+        blocks: Vec<BlueprintBlock>,
+        entities: Vec<Entity>,
+        splits: Vec<(Entity, Transform)>,
+    },
+}
+
+// system state
+#[derive(Default, Resource)]
+pub struct BuildingHistory {
+    undo_stack: Vec<BuildingAction>,
+    redo_stack: Vec<BuildingAction>,
+    // grids merged away or respawned by undo/redo, old entity to replacement
+    // and transform from the old grid's local frame into the replacement's
+    grid_remap: HashMap<Entity, (Entity, Transform)>,
+    // blocks respawned by undo/redo, old entity to replacement
+    block_remap: HashMap<Entity, Entity>,
+}
+
+impl BuildingHistory {
+    // records a new action, discarding redo history
+    pub fn record(&mut self, action: BuildingAction) {
+        self.undo_stack.push(action);
+        if self.undo_stack.len() > HISTORY_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    // follows grids absorbed by merging, keeping their blocks placeable
+    pub fn record_merges(&mut self, merges: &[GridMerge]) {
+        for merge in merges {
+            self.grid_remap.insert(merge.absorbed, (merge.target, merge.to_target));
+        }
+    }
+
+    // moves recorded grid transforms along with the world origin
+    pub fn shift(&mut self, offset: Vec3) {
+        for action in self.undo_stack.iter_mut().chain(self.redo_stack.iter_mut()) {
+            match action {
+                BuildingAction::Place { grid_transform, .. } |
+                BuildingAction::Remove { grid_transform, .. } => grid_transform.translation += offset,
+            }
+        }
+    }
+
+    // current grid and transform into its local frame
+    fn resolve_grid(&self, grid: Entity) -> (Entity, Transform) {
+        let (mut grid, mut to_grid) = (grid, Transform::IDENTITY);
+        while let Some((next, to_next)) = self.grid_remap.get(&grid) {
+            grid = *next;
+            to_grid = to_next.mul_transform(to_grid);
+        }
+        (grid, to_grid)
+    }
+
+    fn resolve_block(&self, block: Entity) -> Entity {
+        let mut block = block;
+        while let Some(next) = self.block_remap.get(&block) {
+            block = *next;
+        }
+        block
+    }
+}
+
+/// Undo/redo of block placement and removal
+pub struct BuildingHistoryPlugin;
+
+impl Plugin for BuildingHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(BuildingHistory::default());
+
+        app.add_systems(Update, shift_building_history.before(update_building_history));
+        app.add_systems(Update, update_building_history.run_if(in_state(GameState::Running)));
+    }
+}
+
+fn shift_building_history(
+    mut history: ResMut<BuildingHistory>,
+    mut shift_events: EventReader<OriginShiftEvent>,
+) {
+    for shift in shift_events.read() {
+        history.shift(shift.offset);
+    }
+}
+
+fn update_building_history(
+    mut commands: Commands,
+    key_state: Res<KeyInputState>,
+    infos: Res<BpInfos>,
+    mut history: ResMut<BuildingHistory>,
+    grids_query: Query<(Entity, &GlobalTransform, &Grid, Option<&Velocity>, Option<&ReadMassProperties>)>,
+    grid_bodies_query: Query<(&Transform, Option<&Velocity>, Option<&ReadMassProperties>), With<Grid>>,
+    gb_query: Query<(Entity, &GridBlock)>,
+    mut snaps_events: EventWriter<BpSnapsEvent>,
+) {
+    let (action, undo) = if key_state.undo {
+        (history.undo_stack.pop(), true)
+    } else if key_state.redo {
+        (history.redo_stack.pop(), false)
+    } else {
+        return;
+    };
+    let Some(mut action) = action else {
+        return;
+    };
+
+    // undoing a removal or redoing a placement places the blocks again
+    let place = matches!(action, BuildingAction::Remove { .. }) == undo;
+    let (grid, grid_transform, blocks, entities) = match &action {
+        BuildingAction::Place { grid, grid_transform, blocks, entities, .. } |
+        BuildingAction::Remove { grid, grid_transform, blocks, entities, .. } => (*grid, *grid_transform, blocks, entities),
+    };
+
+    if place {
+        // follow merges of the recorded grid, respawning it if it was despawned with its last block
+        let (grid_entity, to_grid) = history.resolve_grid(grid);
+        let (grid_entity, grid_transform, to_grid) = match grids_query.get(grid_entity) {
+            Ok((_, gt, ..)) => (grid_entity, gt.compute_transform(), to_grid),
+            Err(_) => {
+                let new_grid = spawn_grid(&mut commands, grid_transform);
+                history.grid_remap.insert(grid_entity, (new_grid, Transform::from_matrix(to_grid.compute_matrix().inverse())));
+                (new_grid, grid_transform, Transform::IDENTITY)
+            }
+        };
+        let blocks: Vec<BlueprintBlock> = blocks.iter()
+            .map(|block| BlueprintBlock {
+                position: DiscreteVec3::from(to_grid.transform_point(block.position.to_vec3())),
+                rotation: snap_right_angle(to_grid.rotation * block.rotation),
+                ..block.clone()
+            })
+            .collect();
+
+        // grids merged by the placement or split off by the removal join the grid again
+        let rejoined: Vec<(Entity, Transform)> = match &action {
+            BuildingAction::Place { merges, .. } => merges.iter().map(|merge| (merge.absorbed, merge.to_target)).collect(),
+            BuildingAction::Remove { splits, .. } => splits.clone(),
+        };
+        let restored_grids: Vec<(Entity, Transform)> = rejoined.into_iter()
+            .filter_map(|(old_grid, to_recorded)| {
+                let (other_grid, to_other) = history.resolve_grid(old_grid);
+                (other_grid != grid_entity && grids_query.contains(other_grid)).then(|| (other_grid, Transform::from_matrix(
+                    to_grid.compute_matrix() * to_recorded.compute_matrix() * to_other.compute_matrix().inverse())))
+            })
+            .collect();
+
+        let (block_entities, merges) = place_grid_blocks(&mut commands, grid_entity, &grid_transform, &blocks,
+            &infos, &grids_query, &gb_query, &mut snaps_events, &restored_grids);
+        history.record_merges(&merges);
+        for (old_block, new_block) in entities.iter().zip(block_entities) {
+            let old_block = history.resolve_block(*old_block);
+            history.block_remap.insert(old_block, new_block);
+        }
+        if let BuildingAction::Place { merges: recorded_merges, .. } = &mut action {
+            let from_grid = to_grid.compute_matrix().inverse();
+            *recorded_merges = merges.into_iter()
+                .map(|merge| GridMerge {
+                    target: grid,
+                    to_target: Transform::from_matrix(from_grid * merge.to_target.compute_matrix()),
+                    ..merge
+                })
+                .collect();
+        }
+    } else {
+        // blocks may have moved to other grids by merging or splitting since
+        let mut grid_blocks: HashMap<Entity, Vec<Entity>> = HashMap::default();
+        for block in entities.iter().map(|block| history.resolve_block(*block)) {
+            if let Ok((_, gb)) = gb_query.get(block) {
+                grid_blocks.entry(gb.entity).or_default().push(block);
+                commands.entity(block).despawn_recursive();
+            }
+        }
+
+        // grids merged by an undone placement are moved back out, latest first
+        let (grid_entity, to_grid) = history.resolve_grid(grid);
+        if let BuildingAction::Place { merges, .. } = &action {
+            for merge in merges.iter().rev() {
+                let merge = GridMerge {
+                    absorbed: merge.absorbed,
+                    target: grid_entity,
+                    to_target: to_grid.mul_transform(merge.to_target),
+                    blocks: merge.blocks.iter()
+                        .map(|block| history.resolve_block(*block))
+                        .filter(|block| gb_query.get(*block).is_ok_and(|(_, gb)| gb.entity == grid_entity))
+                        .collect(),
+                };
+                if merge.blocks.is_empty() {
+                    continue;
+                }
+                let restored_grid = unmerge_grid(&mut commands, &merge, &infos, &grid_bodies_query, &gb_query, &mut snaps_events);
+                history.grid_remap.insert(merge.absorbed, (restored_grid, Transform::IDENTITY));
+                grid_blocks.entry(grid_entity).or_default().extend(merge.blocks);
+            }
+        }
+
+        let mut splits = vec![];
+        for (split_from, detached) in grid_blocks {
+            // split grids share the frame of the grid they were split from
+            let to_recorded = match (grids_query.get(grid_entity), grids_query.get(split_from)) {
+                (Ok((_, grid_gt, ..)), Ok((_, from_gt, ..))) => Transform::from_matrix(to_grid.compute_matrix().inverse()
+                    * grid_gt.compute_matrix().inverse() * from_gt.compute_matrix()),
+                _ => Transform::IDENTITY,
+            };
+            splits.extend(detach_grid_blocks(&mut commands, split_from, &detached,
+                &infos, &grid_bodies_query, &gb_query, &mut snaps_events)
+                .into_iter().map(|split_grid| (split_grid, to_recorded)));
+        }
+        if let BuildingAction::Remove { splits: recorded_splits, .. } = &mut action {
+            *recorded_splits = splits;
+        }
+    }
+
+    if undo {
+        history.redo_stack.push(action);
+    } else {
+        history.undo_stack.push(action);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::SystemState;
+    use crate::building::{BpInfo,BpSnapPoint,BpSnapsRepeatEvent,GridSnapPoint,remove_grid_blocks,
+        update_building_bp_snaps,update_building_bp_snaps_repeats};
+
+    #[test]
+    fn merged_grids_resolve_to_target_frame() {
+        let mut world = World::new();
+        let (absorbed, target, respawned) = (world.spawn_empty().id(), world.spawn_empty().id(), world.spawn_empty().id());
+        let to_target = Transform::from_xyz(3., 0., 0.).with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2));
+        let mut history = BuildingHistory::default();
+        history.record_merges(&[GridMerge { absorbed, target, to_target, blocks: vec![] }]);
+
+        let (grid, to_grid) = history.resolve_grid(absorbed);
+        assert_eq!(grid, target);
+        assert!(to_grid.transform_point(Vec3::X).distance(Vec3::new(3., 0., -1.)) < 1E-5);
+
+        // target respawned in the absorbed grid's frame after losing all blocks
+        history.grid_remap.insert(target, (respawned, Transform::from_matrix(to_grid.compute_matrix().inverse())));
+        let (grid, to_grid) = history.resolve_grid(absorbed);
+        assert_eq!(grid, respawned);
+        assert!(to_grid.transform_point(Vec3::X).distance(Vec3::X) < 1E-5);
+    }
+
+    // grids with cube blocks, driven by the history and snaps systems
+    fn history_app() -> App {
+        let mut app = App::new();
+        let snap = [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z].iter()
+            .map(|normal| BpSnapPoint { point: 0.5 * *normal, normal: *normal, ..default() })
+            .collect();
+        let cube = BpInfo { mass: 1., collider: Collider::cuboid(0.5, 0.5, 0.5), snap, ..default() };
+        app.add_plugins((MinimalPlugins, TransformPlugin))
+            .add_event::<BpSnapsEvent>()
+            .add_event::<BpSnapsRepeatEvent>()
+            .insert_resource(BpInfos { bps: [("cube".to_string(), cube)].into(), toolbar_order: vec!["cube".into()] })
+            .init_resource::<BuildingHistory>()
+            .init_resource::<KeyInputState>()
+            .add_systems(Update, (update_building_history, update_building_bp_snaps, update_building_bp_snaps_repeats).chain());
+        app.update();
+        app
+    }
+
+    // places cubes along x, into new grid at transform if none given
+    fn place(app: &mut App, grid: Option<Entity>, grid_transform: Transform, xs: &[i32]) -> (Entity, Vec<Entity>) {
+        let mut state = SystemState::<(
+            Commands,
+            Res<BpInfos>,
+            ResMut<BuildingHistory>,
+            Query<(Entity, &GlobalTransform, &Grid, Option<&Velocity>, Option<&ReadMassProperties>)>,
+            Query<(Entity, &GridBlock)>,
+            EventWriter<BpSnapsEvent>,
+        )>::new(&mut app.world);
+        let (mut commands, infos, mut history, grids_query, gb_query, mut snaps_events) = state.get_mut(&mut app.world);
+        let grid = grid.unwrap_or_else(|| spawn_grid(&mut commands, grid_transform));
+        let blocks: Vec<BlueprintBlock> = xs.iter()
+            .map(|x| BlueprintBlock {
+                part: "cube".into(),
+                position: DiscreteVec3([*x, 0, 0]),
+                rotation: Quat::IDENTITY,
+                settings: default(),
+            })
+            .collect();
+        let (entities, merges) = place_grid_blocks(&mut commands, grid, &grid_transform, &blocks,
+            &infos, &grids_query, &gb_query, &mut snaps_events, &[]);
+        history.record_merges(&merges);
+        history.record(BuildingAction::Place { grid, grid_transform, blocks, entities: entities.clone(), merges });
+        state.apply(&mut app.world);
+        app.update();
+        (grid, entities)
+    }
+
+    fn remove(app: &mut App, grid: Entity, block: Entity) {
+        let mut state = SystemState::<(
+            Commands,
+            Res<BpInfos>,
+            ResMut<BuildingHistory>,
+            Query<(&Transform, Option<&Velocity>, Option<&ReadMassProperties>), With<Grid>>,
+            Query<(Entity, &GridBlock)>,
+            EventWriter<BpSnapsEvent>,
+        )>::new(&mut app.world);
+        let (mut commands, infos, mut history, grids_query, gb_query, mut snaps_events) = state.get_mut(&mut app.world);
+        let grid_transform = *grids_query.get(grid).unwrap().0;
+        let (_, gb) = gb_query.get(block).unwrap();
+        let removed = BlueprintBlock {
+            part: gb.bp_name.clone(),
+            position: DiscreteVec3::from(gb.collider.as_ref().unwrap().0),
+            rotation: Quat::IDENTITY,
+            settings: default(),
+        };
+        let splits = remove_grid_blocks(&mut commands, grid, &[block], &infos, &grids_query, &gb_query, &mut snaps_events);
+        history.record(BuildingAction::Remove {
+            grid,
+            grid_transform,
+            blocks: vec![removed],
+            entities: vec![block],
+            splits: splits.into_iter().map(|split_grid| (split_grid, Transform::IDENTITY)).collect(),
+        });
+        state.apply(&mut app.world);
+        app.update();
+    }
+
+    fn press(app: &mut App, undo: bool) {
+        let mut key_state = app.world.resource_mut::<KeyInputState>();
+        (key_state.undo, key_state.redo) = (undo, !undo);
+        app.update();
+        let mut key_state = app.world.resource_mut::<KeyInputState>();
+        (key_state.undo, key_state.redo) = (false, false);
+        // snaps of respawned grids are inserted a frame later
+        app.update();
+    }
+
+    // solid blocks, snap counts per space, snap entities and collider shape positions of grid
+    #[derive(Debug, PartialEq)]
+    struct GridSnapshot {
+        solid: Vec<[i32; 3]>,
+        snaps: Vec<([i32; 3], usize)>,
+        snap_entities: usize,
+        shapes: Vec<[i32; 3]>,
+    }
+
+    fn snapshot(app: &mut App, grid: Entity) -> GridSnapshot {
+        let grid_component = app.world.get::<Grid>(grid).unwrap();
+        let mut solid: Vec<[i32; 3]> = grid_component.solid_blocks.keys().map(|pos| pos.0).collect();
+        let mut snaps: Vec<([i32; 3], usize)> = grid_component.snaps_for_space.iter()
+            .map(|(pos, snaps)| (pos.0, snaps.len())).collect();
+        let collider = app.world.get::<Collider>(grid).unwrap();
+        let mut shapes: Vec<[i32; 3]> = collider.as_compound().unwrap().shapes()
+            .map(|(position, _, _)| DiscreteVec3::from(position).0).collect();
+        solid.sort();
+        snaps.sort();
+        shapes.sort();
+        let snap_entities = app.world.query::<&GridSnapPoint>().iter(&app.world)
+            .filter(|gsp| gsp.entity == grid).count();
+        GridSnapshot { solid, snaps, snap_entities, shapes }
+    }
+
+    fn grids_with_blocks(app: &mut App) -> Vec<Entity> {
+        let mut grids: Vec<Entity> = app.world.query::<&GridBlock>().iter(&app.world).map(|gb| gb.entity).collect();
+        grids.sort();
+        grids.dedup();
+        grids
+    }
+
+    #[test]
+    fn undo_and_redo_placement_restore_grid() {
+        let mut app = history_app();
+        let (grid, _) = place(&mut app, None, Transform::IDENTITY, &[0, 1]);
+        let before = snapshot(&mut app, grid);
+        assert_eq!(before.solid.len(), 2);
+        place(&mut app, Some(grid), Transform::IDENTITY, &[2]);
+        let after = snapshot(&mut app, grid);
+        assert_ne!(before, after);
+
+        press(&mut app, true);
+        assert_eq!(snapshot(&mut app, grid), before);
+        press(&mut app, false);
+        assert_eq!(snapshot(&mut app, grid), after);
+    }
+
+    #[test]
+    fn undo_removal_rejoins_split_grid() {
+        let mut app = history_app();
+        let (grid, blocks) = place(&mut app, None, Transform::IDENTITY, &[0, 1, 2]);
+        let before = snapshot(&mut app, grid);
+        remove(&mut app, grid, blocks[1]);
+        assert_eq!(grids_with_blocks(&mut app).len(), 2);
+
+        press(&mut app, true);
+        assert_eq!(grids_with_blocks(&mut app), vec![grid]);
+        assert_eq!(snapshot(&mut app, grid), before);
+    }
+
+    #[test]
+    fn undo_placement_unmerges_bridged_grids() {
+        let mut app = history_app();
+        let (grid, _) = place(&mut app, None, Transform::IDENTITY, &[0]);
+        let other_transform = Transform::from_xyz(2., 0., 0.).with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2));
+        let (other_grid, other_blocks) = place(&mut app, None, other_transform, &[0]);
+        let (grid_before, other_before) = (snapshot(&mut app, grid), snapshot(&mut app, other_grid));
+
+        place(&mut app, Some(grid), Transform::IDENTITY, &[1]);
+        assert_eq!(grids_with_blocks(&mut app), vec![grid]);
+        assert_eq!(snapshot(&mut app, grid).solid.len(), 3);
+
+        press(&mut app, true);
+        assert_eq!(grids_with_blocks(&mut app).len(), 2);
+        assert_eq!(snapshot(&mut app, grid), grid_before);
+        let restored_grid = app.world.get::<GridBlock>(other_blocks[0]).unwrap().entity;
+        assert_ne!(restored_grid, grid);
+        assert_eq!(snapshot(&mut app, restored_grid), other_before);
+        let restored_transform = app.world.get::<Transform>(restored_grid).unwrap();
+        assert!(restored_transform.translation.distance(other_transform.translation) < 1E-5);
+        assert!(restored_transform.rotation.mul_vec3(Vec3::X).distance(other_transform.rotation.mul_vec3(Vec3::X)) < 1E-5);
+
+        press(&mut app, false);
+        assert_eq!(grids_with_blocks(&mut app), vec![grid]);
+        assert_eq!(snapshot(&mut app, grid).solid.len(), 3);
+    }
+}
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-use crate::building::{BpInfo,BpSnapPoint,DiscreteVec3,Grid,GridBlock};
+use crate::building::{BpInfo,BpInfos,BpSnapPoint,DiscreteVec3,Grid,GridBlock};
 use crate::character::CharacterFpsMotionConfig;
 use crate::world::WORLD_GROUP;
 
@@ -17,20 +17,25 @@ pub struct BpSnapResult {
 #[derive(Component,Debug)]
 pub struct GridSnapPoint {
     pub entity: Entity,
+    pub block: DiscreteVec3,
     pub collider: Option<(Vec3,Quat,Collider)>,
 }
 
 
 #[derive(Clone,Event)]
 pub enum BpSnapsEvent {
-    InsertSnaps(BpInfo, Entity, Transform),
+    InsertSnaps(Box<BpInfo>, Entity, Transform),
+    RemoveSnaps(Entity, Vec<DiscreteVec3>),
 }
 #[derive(Clone,Event)]
 pub struct BpSnapsRepeatEvent(BpSnapsEvent);
 
 pub fn update_building_bp_snaps(
     mut commands: Commands,
+    infos: Res<BpInfos>,
     mut grids_query: Query<&mut Grid>,
+    gsp_query: Query<&GridSnapPoint>,
+    gb_query: Query<&GridBlock>,
     mut snaps_events: EventReader<BpSnapsEvent>,
     mut snaps_events_out: EventWriter<BpSnapsRepeatEvent>,
 ) {
@@ -50,6 +55,21 @@ pub fn update_building_bp_snaps(
                     );
                 }
             },
+            BpSnapsEvent::RemoveSnaps(grid_entity, block_positions) => {
+                // grid was despawned with its last block
+                if !grids_query.contains(*grid_entity) {
+                    continue;
+                }
+                remove_bp_snaps(
+                    &mut commands,
+                    &infos,
+                    *grid_entity,
+                    &mut grids_query,
+                    &gsp_query,
+                    &gb_query,
+                    block_positions,
+                );
+            },
         }
     }
 }
@@ -61,8 +81,19 @@ pub fn insert_bp_snaps(
     grids_query: &mut Query<&mut Grid>,
     target_transform: &Transform,
 ) {
-    let dest_pos = DiscreteVec3::from(target_transform.translation);
     let mut grid = grids_query.get_mut(grid_entity).unwrap();
+    insert_bp_snaps_into(commands, bp_info, grid_entity, &mut grid, target_transform);
+}
+
+// inserts block into grid bookkeeping, replacing snaps for its position
+pub fn insert_bp_snaps_into(
+    commands: &mut Commands,
+    bp_info: &BpInfo,
+    grid_entity: Entity,
+    grid: &mut Grid,
+    target_transform: &Transform,
+) {
+    let dest_pos = DiscreteVec3::from(target_transform.translation);
 
     // update grid solidity
     // todo move to better place
@@ -76,6 +107,19 @@ pub fn insert_bp_snaps(
     }
 
     // insert snaps from bp, ignoring occupied positions
+    spawn_bp_snaps(commands, bp_info, grid_entity, grid, target_transform, None);
+}
+
+// spawns snap colliders for block, optionally only those facing the given position
+fn spawn_bp_snaps(
+    commands: &mut Commands,
+    bp_info: &BpInfo,
+    grid_entity: Entity,
+    grid: &mut Grid,
+    target_transform: &Transform,
+    only_facing: Option<&DiscreteVec3>,
+) {
+    let block_pos = DiscreteVec3::from(target_transform.translation);
     for snap in bp_info.snap.iter() {
         let snap_point = target_transform.rotation.mul_vec3(snap.point);
         let snap_transform = Transform::from_translation(target_transform.translation + snap_point)
@@ -84,6 +128,9 @@ pub fn insert_bp_snaps(
         if grid.solid_blocks.contains_key(&snap_target_pos) {
             continue;
         }
+        if only_facing.is_some_and(|facing| *facing != snap_target_pos) {
+            continue;
+        }
 
         let snap_ent = commands.spawn(SpatialBundle {
             transform: snap_transform,
@@ -94,6 +141,7 @@ pub fn insert_bp_snaps(
             .insert(CollisionGroups::new(SNAPS_GROUP, SNAPS_GROUP))
             .insert(GridSnapPoint { 
                 entity: grid_entity, 
+                block: block_pos.clone(),
                 collider: None, 
             })
             // for debugging
@@ -115,6 +163,51 @@ pub fn insert_bp_snaps(
     }
 }
 
+pub fn remove_bp_snaps(
+    commands: &mut Commands,
+    infos: &BpInfos,
+    grid_entity: Entity,
+    grids_query: &mut Query<&mut Grid>,
+    gsp_query: &Query<&GridSnapPoint>,
+    gb_query: &Query<&GridBlock>,
+    block_positions: &[DiscreteVec3],
+) {
+    let mut grid = grids_query.get_mut(grid_entity).unwrap();
+    for block_pos in block_positions.iter() {
+        grid.solid_blocks.remove(block_pos);
+    }
+
+    // despawn snaps owned by removed blocks
+    for snaps in grid.snaps_for_space.values_mut() {
+        snaps.retain(|snap_ent| {
+            let owned = gsp_query.get(*snap_ent).is_ok_and(|gsp| block_positions.contains(&gsp.block));
+            if owned {
+                commands.entity(*snap_ent).despawn_recursive();
+            }
+            !owned
+        });
+    }
+    grid.snaps_for_space.retain(|_, snaps| !snaps.is_empty());
+
+    // regenerate snaps of remaining neighbouring blocks facing the freed positions
+    for block_pos in block_positions.iter() {
+        let neighbours = block_pos.neighbours();
+        for gb in gb_query.iter().filter(|gb| gb.entity == grid_entity) {
+            let Some((translation, rotation, _)) = gb.collider.as_ref() else {
+                continue;
+            };
+            let gb_pos = DiscreteVec3::from(*translation);
+            if !neighbours.contains(&gb_pos) || !grid.solid_blocks.contains_key(&gb_pos) {
+                continue;
+            }
+            if let Some(bp_info) = infos.bps.get(&gb.bp_name) {
+                let block_transform = Transform::from_translation(*translation).with_rotation(*rotation);
+                spawn_bp_snaps(commands, bp_info, grid_entity, &mut grid, &block_transform, Some(block_pos));
+            }
+        }
+    }
+}
+
 pub fn cast_snaps_ray(
     // mut world_state: ResMut<WorldState>,
     // mover_parent_query: Query<&GlobalTransform, With<MoverParent>>,
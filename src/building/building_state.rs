@@ -1,25 +1,31 @@
 use crate::game_state::GameState;
-use crate::loading::WorldProps;
-use crate::actions::BuildingActionsState;
-use crate::inputs::MouseLookState;
-use crate::building::{BpInfo,BpInfos,BpSnapPoint,BpSnapsEvent,BpSnapsRepeatEvent,
-    find_or_create_grid,GridBlock,GridSnapPoint,
+use crate::loading::{PartsAssets,WorldProps};
+use crate::actions::{BuildingActionsState,BuildingSymmetry,HammerTool};
+use crate::inputs::{InputAction,MouseLookState,user_data_dir};
+use crate::building::{BlockSettings,Blueprint,BlueprintBlock,BpInfo,BpInfos,BpSnapPoint,BpSnapsEvent,BpSnapsRepeatEvent,
+    BuildingAction,BuildingHistory,BuildingHistoryPlugin,
+    find_or_create_grid,find_grid_block,place_grid_blocks,
+    DiscreteVec3,Grid,GridBlock,GridSnapPoint,remove_grid_blocks,
     update_building_bp_snaps,update_building_bp_snaps_repeats,cast_snaps_ray,
-    BuildingToolbarPlugin,BUILD_DIST};
-use crate::props::{InteractableInfo,INTERACT_GROUP,ThrusterInteractable};
+    BuildingToolbarPlugin,PartsAsset,BUILD_DIST};
 use crate::character::CharacterFpsMotionConfig;
-use crate::world::WorldLoadingState;
+use crate::props::is_staged_part;
+use crate::world::{WorldLoadingState,WORLD_GROUP};
 
 use bevy::{prelude::*, gltf::Gltf};
 use bevy_rapier3d::prelude::*;
 
 const SNAP_DELAY: f32 = 0.3;
+#[cfg(not(target_arch = "wasm32"))]
+const QUICKSAVE_PATH: &str = "blueprints/quicksave.blueprint.ron";
 
 // system state
 #[derive(Default, Resource)]
 pub struct BuildingState {
     pub active_index: usize,
     pub shown_bp_entity: Option<Entity>,
+    // ghost previews of symmetric counterparts
+    symmetry_bp_entities: Vec<Entity>,
     cast_result: BpCastResult,
     last_cast_time: Timer,
 }
@@ -29,13 +35,16 @@ impl Plugin for BuildingStatePlugin {
     fn build(&self, app: &mut App) {
         app
         .insert_resource(BuildingState::default())
-        .insert_resource(BpInfos::default())
         .add_event::<BpSnapsEvent>()
         .add_event::<BpSnapsRepeatEvent>()
-        .add_plugins((BuildingToolbarPlugin::default(),))
+        .add_plugins((BuildingToolbarPlugin::default(),BuildingHistoryPlugin,))
+        .add_systems(OnExit(GameState::AssetLoading), setup_bp_infos)
         // .add_systems(OnEnter(GameState::WorldLoading), setup_building_interactive_states)
         .add_systems(Update, (
             update_building_state.run_if(in_state(GameState::Running)),
+            update_grid_block_scenes.run_if(in_state(GameState::Running)),
+            update_unbuild_state.run_if(in_state(GameState::Running)),
+            update_blueprint_quicksave.run_if(in_state(GameState::Running)),
             update_building_bp_transform.run_if(in_state(GameState::Running)),
             update_building_bp_snaps.run_if(in_state(GameState::Running)),
             update_building_bp_snaps_repeats.run_if(in_state(GameState::Running)),
@@ -43,16 +52,29 @@ impl Plugin for BuildingStatePlugin {
     }
 }
 
+fn setup_bp_infos(
+    mut commands: Commands,
+    parts_assets: Res<PartsAssets>,
+    parts: Res<Assets<PartsAsset>>,
+) {
+    // loading only continues once the parts loaded, the loader rejects invalid definitions
+    let parts_asset = parts.get(&parts_assets.building_kit).expect("part definitions loaded");
+    commands.insert_resource(parts_asset.infos.clone());
+}
+
 fn update_building_state(
     mut commands: Commands,
     assets_gltf: Res<Assets<Gltf>>,
     world_props: Res<WorldProps>,
     mut world_loading: ResMut<WorldLoadingState>,
     mouse_btn_input: Res<Input<MouseButton>>,
+    action_input: Res<Input<InputAction>>,
     mut building_state: ResMut<BuildingState>,
     building_actions: Res<BuildingActionsState>,
     infos: Res<BpInfos>,
-    gb_query: Query<&GridBlock>,
+    gb_query: Query<(Entity, &GridBlock)>,
+    grids_query: Query<(Entity, &GlobalTransform, &Grid, Option<&Velocity>, Option<&ReadMassProperties>)>,
+    mut building_history: ResMut<BuildingHistory>,
     mut snaps_events: EventWriter<BpSnapsEvent>,
     mut transforms_query: Query<(&mut Transform, Without<CharacterFpsMotionConfig>)>,    
 ) {    
@@ -65,17 +87,20 @@ fn update_building_state(
             commands.entity(shown_bp_entity).insert(Visibility::Hidden);
             building_state.shown_bp_entity = None;
         }
+        for symmetry_bp_entity in building_state.symmetry_bp_entities.iter() {
+            commands.entity(*symmetry_bp_entity).insert(Visibility::Hidden);
+        }
         
         return;
     }
 
     // get projected position/rotation from collision raycast
-    let scene_name = &building_kit_names[building_actions.active_index];
-    let bp_info = infos.bps[&scene_name.to_string()].clone();
+    let bp_name = &building_kit_names[building_actions.active_index];
+    let bp_info = infos.bps[bp_name].clone();
     let grid_transform = building_state.cast_result.grid_transform;
 
     // show bp model if not shown
-    let bp_scene_name: String = scene_name.to_owned() + "_bp";
+    let bp_scene_name: String = bp_info.scene.to_owned() + "_bp";
     if world_loading.build_kit_preload_ent.is_none() {
         world_loading.build_kit_preload_ent = 
             spawn_gltf_instance(bp_scene_name.as_str(), 
@@ -88,61 +113,204 @@ fn update_building_state(
     if building_actions.active_index != building_state.active_index {
         if let Some(gltf) = assets_gltf.get(&world_props.building_kit) {
             commands.entity(world_loading.build_kit_preload_ent.unwrap()).insert(gltf.named_scenes[&bp_scene_name].clone());
+            for symmetry_bp_entity in building_state.symmetry_bp_entities.iter() {
+                commands.entity(*symmetry_bp_entity).insert(gltf.named_scenes[&bp_scene_name].clone());
+            }
         }
         building_state.active_index = building_actions.active_index;
     }
 
-    if mouse_btn_input.just_pressed(MouseButton::Left) {
-        let scene_name = &building_kit_names[building_state.active_index];
-        if let Some(gltf) = assets_gltf.get(&world_props.building_kit) {
-            let (grid_entity, _grid_transform) = find_or_create_grid(
-                &mut commands, building_state.cast_result.grid_entity, grid_transform, &mut transforms_query);
-
-            // insert solid gltf entity, colliders
-            let local_translation = building_state.cast_result.local_transform.translation;
-            let rot_quat = building_state.cast_result.local_transform.rotation;
-            let sb = SceneBundle {
-                scene: gltf.named_scenes[scene_name].clone(),
-                transform: Transform::from_translation(local_translation).with_rotation(rot_quat),
-                ..Default::default()
-            };
-            let mut build_block = commands.spawn(sb);
-            build_block.insert(AdditionalMassProperties::Mass(2.));
-            build_block.insert(GridBlock { 
-                entity: grid_entity, 
-                collider: Some((local_translation, rot_quat, bp_info.collider.clone())), 
-            });
-            build_block.set_parent(grid_entity);
-            // add block interactable extras
-            if scene_name == "flight_seat" {
-                build_block.with_children(|parent| {
-                    parent.spawn(SpatialBundle {
-                        transform: Transform::from_translation(local_translation).with_rotation(rot_quat),
-                        ..default()
-                    })
-                        .insert(InteractableInfo { hover_text: "Enter Flight Seat (F)".into() })
-                        .insert(bp_info.collider.clone())
-                        .insert(CollisionGroups::new(INTERACT_GROUP, INTERACT_GROUP));
-                });
-            }
-            if scene_name == "thruster" {
-                build_block.insert(ThrusterInteractable { grid: Some(grid_entity) });
-            }
-            
-            // insert building tool snap colliders
-            snaps_events.send(BpSnapsEvent::InsertSnaps(
-                bp_info.clone(), grid_entity, Transform::from_translation(local_translation).with_rotation(rot_quat)));
-
-            // update collider entities
-            let mut colliders: Vec<(Vec3,Quat,Collider)> = gb_query.iter()
-                .filter(|gb|{ gb.entity == grid_entity && gb.collider.is_some()})
-                .map(|gb| { gb.collider.clone().unwrap() }).collect();
-            colliders.push((local_translation, rot_quat, bp_info.collider.clone()));
-            commands.entity(grid_entity).insert(Collider::compound(
-                colliders
-            ));
+    // symmetric counterparts, only when snapped to an existing grid
+    let local_transform = building_state.cast_result.local_transform;
+    let counterparts = building_state.cast_result.grid_entity
+        .and_then(|grid_entity| grids_query.get(grid_entity).ok())
+        .map(|(_, _, grid, _, _)| symmetric_placements(building_actions.symmetry, grid, &local_transform))
+        .unwrap_or_default();
+
+    // show ghost for each counterpart
+    while building_state.symmetry_bp_entities.len() < counterparts.len() {
+        let Some(ghost) = spawn_gltf_instance(bp_scene_name.as_str(),
+                &mut commands, &assets_gltf, &world_props, grid_transform) else {
+            break;
+        };
+        building_state.symmetry_bp_entities.push(ghost);
+    }
+    for (idx, symmetry_bp_entity) in building_state.symmetry_bp_entities.iter().enumerate() {
+        if let Some(counterpart) = counterparts.get(idx) {
+            commands.entity(*symmetry_bp_entity)
+                .insert(grid_transform.mul_transform(*counterpart))
+                .insert(Visibility::Visible);
+        } else {
+            commands.entity(*symmetry_bp_entity).insert(Visibility::Hidden);
         }
     }
+
+    if mouse_btn_input.just_pressed(MouseButton::Left) || action_input.just_pressed(InputAction::ToolUse) {
+        let bp_name = &building_kit_names[building_state.active_index];
+        let (grid_entity, grid_transform) = find_or_create_grid(
+            &mut commands, building_state.cast_result.grid_entity, grid_transform, &mut transforms_query);
+
+        // insert solid block entities, snaps, colliders
+        let mut stage_settings = BlockSettings::default();
+        if is_staged_part(bp_name) && building_actions.active_stage > 0 {
+            stage_settings.0.insert("stage".to_string(), building_actions.active_stage as f32);
+        }
+        let blocks: Vec<BlueprintBlock> = std::iter::once(local_transform).chain(counterparts)
+            .map(|transform| BlueprintBlock {
+                part: bp_name.clone(),
+                position: DiscreteVec3::from(transform.translation),
+                rotation: transform.rotation,
+                settings: stage_settings.clone(),
+            })
+            .collect();
+        let (entities, merges) = place_grid_blocks(&mut commands, grid_entity, &grid_transform, &blocks,
+            &infos, &grids_query, &gb_query, &mut snaps_events, &[]);
+        building_history.record_merges(&merges);
+        building_history.record(BuildingAction::Place {
+            grid: grid_entity,
+            grid_transform,
+            blocks,
+            entities,
+            merges,
+        });
+    }
+}
+
+// counterparts of placement under symmetry, skipping occupied and duplicate cells
+fn symmetric_placements(
+    symmetry: BuildingSymmetry,
+    grid: &Grid,
+    local_transform: &Transform,
+) -> Vec<Transform> {
+    let mut used = vec![DiscreteVec3::from(local_transform.translation)];
+    let mut placements = vec![];
+    for counterpart in symmetry.counterparts(local_transform) {
+        let block_pos = DiscreteVec3::from(counterpart.translation);
+        if grid.solid_blocks.contains_key(&block_pos) || used.contains(&block_pos) {
+            continue;
+        }
+        placements.push(counterpart.with_translation(block_pos.to_vec3()));
+        used.push(block_pos);
+    }
+    placements
+}
+
+// attaches gltf scenes to blocks spawned without one
+fn update_grid_block_scenes(
+    mut commands: Commands,
+    assets_gltf: Res<Assets<Gltf>>,
+    world_props: Res<WorldProps>,
+    infos: Res<BpInfos>,
+    gb_query: Query<(Entity, &GridBlock), Without<Handle<Scene>>>,
+) {
+    let Some(gltf) = assets_gltf.get(&world_props.building_kit) else {
+        return;
+    };
+    for (block_entity, gb) in gb_query.iter() {
+        if let Some(scene) = infos.bps.get(&gb.bp_name).and_then(|bp_info| gltf.named_scenes.get(&bp_info.scene)) {
+            commands.entity(block_entity).insert(scene.clone());
+        }
+    }
+}
+
+// saves targeted grid as blueprint, or spawns last saved blueprint at build target
+#[cfg(not(target_arch = "wasm32"))]
+fn update_blueprint_quicksave(
+    mut commands: Commands,
+    action_input: Res<Input<InputAction>>,
+    building_state: Res<BuildingState>,
+    building_actions: Res<BuildingActionsState>,
+    infos: Res<BpInfos>,
+    gb_query: Query<(&GridBlock, Option<&BlockSettings>)>,
+) {
+    if !building_actions.building_active {
+        return;
+    }
+    let path = &user_data_dir().join(QUICKSAVE_PATH);
+
+    if action_input.just_pressed(InputAction::Quicksave) {
+        let Some(grid_entity) = building_state.cast_result.grid_entity else {
+            return;
+        };
+        match Blueprint::from_grid(grid_entity, &gb_query).save(path) {
+            Ok(()) => info!("Saved blueprint: {:?}", path),
+            Err(err) => error!("Failed to save blueprint: {}", err),
+        }
+    }
+
+    if action_input.just_pressed(InputAction::Quickload) {
+        let target = building_state.cast_result.grid_transform.mul_transform(
+            building_state.cast_result.local_transform);
+        let spawned = Blueprint::load(path)
+            .and_then(|blueprint| blueprint.spawn(&mut commands, &infos, target));
+        if let Err(err) = spawned {
+            error!("Failed to load blueprint: {}", err);
+        }
+    }
+}
+#[cfg(target_arch = "wasm32")]
+fn update_blueprint_quicksave() { }
+
+fn update_unbuild_state(
+    mut commands: Commands,
+    mouse_btn_input: Res<Input<MouseButton>>,
+    action_input: Res<Input<InputAction>>,
+    building_actions: Res<BuildingActionsState>,
+    mouse_look: Res<MouseLookState>,
+    mover_query: Query<&Transform, With<CharacterFpsMotionConfig>>,
+    rapier_context: Res<RapierContext>,
+    infos: Res<BpInfos>,
+    grids_query: Query<(&Transform, Option<&Velocity>, Option<&ReadMassProperties>), With<Grid>>,
+    gb_query: Query<(Entity, &GridBlock)>,
+    settings_query: Query<&BlockSettings>,
+    mut building_history: ResMut<BuildingHistory>,
+    mut snaps_events: EventWriter<BpSnapsEvent>,
+) {
+    // only while unbuild tool is held
+    if building_actions.building_active || building_actions.active_tool != HammerTool::Unbuild ||
+        !(mouse_btn_input.just_pressed(MouseButton::Left) || action_input.just_pressed(InputAction::ToolUse)) {
+        return;
+    }
+
+    // raycast against grid colliders
+    let Ok(mover_transform) = mover_query.get_single() else {
+        return;
+    };
+    let cast_origin = mover_transform.translation + 0.4 * Vec3::Y;
+    let ray_groups = CollisionGroups::new(WORLD_GROUP, WORLD_GROUP);
+    let ray_filter: QueryFilter<'_> = QueryFilter { groups: Some(ray_groups), ..Default::default()};
+    let Some((collided_entity, intersection)) = rapier_context.cast_ray_and_get_normal(
+            cast_origin, mouse_look.forward, BUILD_DIST, true, ray_filter
+    ) else {
+        return;
+    };
+    let Ok((grid_transform, _, _)) = grids_query.get(collided_entity) else {
+        return;
+    };
+
+    // find block containing point just inside the hit face
+    let local_point = grid_transform.compute_affine().inverse().transform_point3(
+        intersection.point - 0.25 * intersection.normal);
+    let block_pos = DiscreteVec3::from(local_point);
+    if let Some(block_entity) = find_grid_block(&gb_query, collided_entity, &block_pos) {
+        let (_, gb) = gb_query.get(block_entity).unwrap();
+        let removed = BlueprintBlock {
+            part: gb.bp_name.clone(),
+            position: block_pos,
+            rotation: gb.collider.as_ref().unwrap().1,
+            settings: settings_query.get(block_entity).ok().cloned().unwrap_or_default(),
+        };
+        // split grids start out in the frame of the grid they were split from
+        let splits = remove_grid_blocks(&mut commands, collided_entity, &[block_entity],
+            &infos, &grids_query, &gb_query, &mut snaps_events);
+        building_history.record(BuildingAction::Remove {
+            grid: collided_entity,
+            grid_transform: *grid_transform,
+            blocks: vec![removed],
+            entities: vec![block_entity],
+            splits: splits.into_iter().map(|split_grid| (split_grid, Transform::IDENTITY)).collect(),
+        });
+    }
 }
 
 fn update_building_bp_transform(
@@ -164,13 +332,13 @@ fn update_building_bp_transform(
         return;
     }
     
-    let building_kit_names = infos.bps.keys().collect::<Vec<&String>>();
-
     // raycast
     if let Some(shown_bp_entity) = building_state.shown_bp_entity {
-        let (mover_transform, _mover) = mover_query.single();
-        let scene_name = building_kit_names[building_state.active_index];
-        let bp_info = &infos.bps[&scene_name.to_string()];
+        let Ok((mover_transform, _mover)) = mover_query.get_single() else {
+            return;
+        };
+        let bp_name = &infos.toolbar_order[building_state.active_index];
+        let bp_info = &infos.bps[bp_name];
         building_state.cast_result = cast_build_shape(
             mouse_look.forward, mover_transform, 
             bp_info, &rapier_context, snaps_query, &gsp_query, &gb_query,
@@ -279,3 +447,24 @@ fn cast_build_shape(
         None,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::building::GridSolidity;
+
+    #[test]
+    fn placements_skip_original_and_occupied_cells() {
+        let mut grid = Grid::default();
+        // on the mirror plane the counterpart is the original cell
+        let on_plane = Transform::from_xyz(0., 1., 2.);
+        assert!(symmetric_placements(BuildingSymmetry::MirrorX, &grid, &on_plane).is_empty());
+
+        let off_center = Transform::from_xyz(2., 0., 0.);
+        assert_eq!(symmetric_placements(BuildingSymmetry::Radial(4), &grid, &off_center).len(), 3);
+        grid.solid_blocks.insert(DiscreteVec3::from(Vec3::new(-2., 0., 0.)), GridSolidity::Solid);
+        let placements = symmetric_placements(BuildingSymmetry::Radial(4), &grid, &off_center);
+        assert_eq!(placements.len(), 2);
+        assert!(placements.iter().all(|placement| placement.translation.x.abs() < 0.5));
+    }
+}
@@ -1,5 +1,7 @@
-use crate::actions::BuildingActionsState;
+use crate::actions::{BuildingActionsState,BuildingSymmetry,HammerTool};
+use crate::building::BpInfos;
 use crate::game_state::GameState;
+use crate::inputs::{InputAction,KeyInputMap,TouchControl};
 use crate::loading::{FontAssets,IconAssets};
 use bevy::prelude::*;
 
@@ -16,10 +18,13 @@ pub struct BuildingToolbarState {
     pub ui_entity: Option<Entity>,
     pub bps_active: bool,
     pub active_index: Option<usize>,
+    pub active_tool: Option<HammerTool>,
     pub toolbar_build_select_text: Option<Entity>,
     pub toolbar_hammer_select_text: Option<Entity>,
     pub toolbar_build_ent: Option<Entity>,
     pub toolbar_hammer_ent: Option<Entity>,
+    pub symmetry: Option<BuildingSymmetry>,
+    pub active_stage: Option<u32>,
 }
 
 // Tag for UI component
@@ -29,6 +34,8 @@ struct BuildingToolbarText;
 // Tag for UI component
 #[derive(Component)]
 pub struct ToolbarItemComp {
+    // item of the hammer toolbar, else of the build toolbar
+    pub hammer: bool,
     pub toolbar_index: usize,
 }
 
@@ -36,6 +43,14 @@ pub struct ToolbarItemComp {
 #[derive(Component)]
 pub struct ThrustersStatusText;
 
+// Tag for UI component
+#[derive(Component)]
+struct SymmetryStatusText;
+
+// Tag for UI component
+#[derive(Component)]
+struct StageStatusText;
+
 #[derive(Default)]
 pub struct BuildingToolbarPlugin;
 
@@ -54,8 +69,11 @@ fn setup_building_toolbar(
     mut commands: Commands,
     font_assets: Res<FontAssets>,
     icon_assets: Res<IconAssets>,
+    infos: Res<BpInfos>,
+    input_map: Res<KeyInputMap>,
     mut system_state: ResMut<BuildingToolbarState>,
 ) {
+    let key = |action| input_map.binding(action).label();
     let toolbar_tools = vec![
         ToolbarItem {
             icon: Some(icon_assets.hammer.clone()),
@@ -75,29 +93,17 @@ fn setup_building_toolbar(
         },
     ];
 
-    let toolbar_bps = vec![
-        ToolbarItem {
-            icon: Some(icon_assets.metal_frame.clone()),
-            text: "Metal Frame".into(),
-        },
+    let toolbar_bps: Vec<ToolbarItem> = infos.toolbar_order.iter().map(|bp_name| {
+        let bp_info = &infos.bps[bp_name];
         ToolbarItem {
-            icon: Some(icon_assets.fuel_tank.clone()),
-            text: "Fuel Tank".into(),
-        },
-        ToolbarItem {
-            icon: Some(icon_assets.thruster.clone()),
-            text: "Thruster".into(),
-        },
-        ToolbarItem {
-            icon: Some(icon_assets.nose_cone.clone()),
-            text: "Nose Cone".into(),
-        },
-        ToolbarItem {
-            icon: Some(icon_assets.flight_seat.clone()),
-            text: "Flight Seat".into(),
-        },
-    ];
+            icon: Some(bp_info.icon.clone()),
+            text: bp_info.title.clone(),
+        }
+    }).collect();
 
+    // refresh status text on next update
+    system_state.symmetry = None;
+    system_state.active_stage = None;
     system_state.ui_entity = Some(commands
         // column for rows of ui elements
         .spawn(NodeBundle {
@@ -164,7 +170,7 @@ fn setup_building_toolbar(
                             },
                             text: Text {
                                 sections: vec![TextSection {
-                                    value: "B - Build".to_string(),
+                                    value: format!("{} - Build", key(InputAction::BuildToggle)),
                                     style: TextStyle {
                                         font: font_assets.fira_sans.clone(),
                                         font_size: 16.0,
@@ -198,7 +204,7 @@ fn setup_building_toolbar(
                             },
                             text: Text {
                                 sections: vec![TextSection {
-                                    value: "H - Hammer".to_string(),
+                                    value: format!("{} - Hammer", key(InputAction::BuildExit)),
                                     style: TextStyle {
                                         font: font_assets.fira_sans.clone(),
                                         font_size: 16.0,
@@ -272,8 +278,12 @@ fn setup_building_toolbar(
                                                     ..default()
                                                 },
                                             ));
+                                            // tapping selects item like its toolbar key
+                                            if let Some(action) = InputAction::TOOLBAR.get(idx) {
+                                                icon_ent.insert(TouchControl::Button(*action));
+                                            }
                                             icon_ent
-                                            .insert(ToolbarItemComp { toolbar_index: idx })
+                                            .insert(ToolbarItemComp { hammer: true, toolbar_index: idx })
                                             .with_children(|parent| {
                                                 // alt text
                                                 // This UI node takes up no space in the layout and the `Text` component is used by the accessibility module
@@ -369,8 +379,12 @@ fn setup_building_toolbar(
                                                     ..default()
                                                 },
                                             ));
+                                            // tapping selects item like its toolbar key
+                                            if let Some(action) = InputAction::TOOLBAR.get(idx) {
+                                                icon_ent.insert(TouchControl::Button(*action));
+                                            }
                                             icon_ent
-                                            .insert(ToolbarItemComp { toolbar_index: idx })
+                                            .insert(ToolbarItemComp { hammer: false, toolbar_index: idx })
                                             .with_children(|parent| {
                                                 // alt text
                                                 // This UI node takes up no space in the layout and the `Text` component is used by the accessibility module
@@ -442,7 +456,7 @@ fn setup_building_toolbar(
                             },
                             text: Text {
                                 sections: vec![TextSection {
-                                    value: "R/T - Rotate".to_string(),
+                                    value: format!("{}/{} - Rotate", key(InputAction::RotateZ), key(InputAction::RotateY)),
                                     style: TextStyle {
                                         font: font_assets.fira_sans.clone(),
                                         font_size: 16.0,
@@ -476,7 +490,47 @@ fn setup_building_toolbar(
                             },
                             text: Text {
                                 sections: vec![TextSection {
-                                    value: "Z/X - Thrusters".to_string(),
+                                    value: format!("{} - Symmetry", key(InputAction::Symmetry)),
+                                    style: TextStyle {
+                                        font: font_assets.fira_sans.clone(),
+                                        font_size: 16.0,
+                                        color: Color::rgba(0.9, 0.9, 0.9, 0.5),
+                                    },
+                                }],
+                                linebreak_behavior: bevy::text::BreakLineOn::WordBoundary,
+                                alignment: TextAlignment::Left,
+                            },
+                            background_color: Color::NONE.into(),
+                            ..Default::default()
+                        }).insert(SymmetryStatusText);
+                        parent.spawn(TextBundle {
+                            style: Style {
+                                margin: UiRect::all(Val::Px(2.)),
+                                ..default()
+                            },
+                            text: Text {
+                                sections: vec![TextSection {
+                                    value: format!("{}/{} - Stage", key(InputAction::StagePrev), key(InputAction::StageNext)),
+                                    style: TextStyle {
+                                        font: font_assets.fira_sans.clone(),
+                                        font_size: 16.0,
+                                        color: Color::rgba(0.9, 0.9, 0.9, 0.5),
+                                    },
+                                }],
+                                linebreak_behavior: bevy::text::BreakLineOn::WordBoundary,
+                                alignment: TextAlignment::Left,
+                            },
+                            background_color: Color::NONE.into(),
+                            ..Default::default()
+                        }).insert(StageStatusText);
+                        parent.spawn(TextBundle {
+                            style: Style {
+                                margin: UiRect::all(Val::Px(2.)),
+                                ..default()
+                            },
+                            text: Text {
+                                sections: vec![TextSection {
+                                    value: format!("{}/{} - Thrusters", key(InputAction::ThrustersUp), key(InputAction::ThrustersDown)),
                                     style: TextStyle {
                                         font: font_assets.fira_sans.clone(),
                                         font_size: 16.0,
@@ -519,9 +573,12 @@ fn setup_building_toolbar(
 
 fn update_building_toolbar(
     building_actions: Res<BuildingActionsState>,
+    input_map: Res<KeyInputMap>,
     mut system_state: ResMut<BuildingToolbarState>,
     mut icon_nodes: Query<(&ToolbarItemComp, &mut BackgroundColor)>,
     mut text_comps: Query<(Entity, &mut Text)>,
+    symmetry_text: Query<Entity, With<SymmetryStatusText>>,
+    stage_text: Query<Entity, With<StageStatusText>>,
     mut vis_comps: Query<(Entity, &mut Visibility)>,
 ) {
     if building_actions.building_active != system_state.bps_active {
@@ -559,10 +616,32 @@ fn update_building_toolbar(
         // }
     }
 
-    if Some(building_actions.active_index) != system_state.active_index {
+    if Some(building_actions.symmetry) != system_state.symmetry {
+        system_state.symmetry = Some(building_actions.symmetry);
+        for text_entity in symmetry_text.iter() {
+            if let Ok((_, mut text)) = text_comps.get_mut(text_entity) {
+                text.sections[0].value = format!("{} - Symmetry: {}", input_map.binding(InputAction::Symmetry).label(), building_actions.symmetry.label());
+            }
+        }
+    }
+
+    if Some(building_actions.active_stage) != system_state.active_stage {
+        system_state.active_stage = Some(building_actions.active_stage);
+        for text_entity in stage_text.iter() {
+            if let Ok((_, mut text)) = text_comps.get_mut(text_entity) {
+                text.sections[0].value = format!("{}/{} - Stage: {}", input_map.binding(InputAction::StagePrev).label(),
+                    input_map.binding(InputAction::StageNext).label(), building_actions.active_stage);
+            }
+        }
+    }
+
+    if Some(building_actions.active_index) != system_state.active_index ||
+        Some(building_actions.active_tool) != system_state.active_tool {
         system_state.active_index = Some(building_actions.active_index);
+        system_state.active_tool = Some(building_actions.active_tool);
         for (bp_item, mut icon_color) in icon_nodes.iter_mut() {
-            if bp_item.toolbar_index == building_actions.active_index {
+            let active_index = if bp_item.hammer { building_actions.active_tool.index() } else { building_actions.active_index };
+            if bp_item.toolbar_index == active_index {
                 icon_color.0 = Color::rgba(1., 1., 1., 1.,).into();
             } else {
                 icon_color.0 = Color::rgba(0.1, 0.3, 0.7, 0.4).into();
@@ -1,10 +1,16 @@
+mod blueprint;
+pub use blueprint::*;
 mod bp_info;
 pub use bp_info::*;
 mod building_grids;
 pub use building_grids::*;
+mod building_history;
+pub use building_history::*;
 mod building_snaps;
 pub use building_snaps::*;
 mod building_state;
 pub use building_state::*;
 mod building_toolbar;
 pub use building_toolbar::*;
+mod parts_asset_loader;
+pub use parts_asset_loader::*;
@@ -0,0 +1,234 @@
+use bevy::{
+    asset::{AssetLoader, io::Reader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use bevy::utils::thiserror;
+use thiserror::Error;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+use std::collections::{HashMap,HashSet};
+
+use crate::building::{BpInfo,BpInfos,BpSnapPoint,GridSolidity};
+
+#[derive(Asset, TypePath)]
+pub struct PartsAsset {
+    pub infos: BpInfos,
+}
+
+// represents the contents of a .parts.ron file
+#[derive(Deserialize)]
+pub struct PartsDef {
+    pub parts: Vec<PartDef>,
+    pub toolbar_order: Vec<String>,
+}
+
+// represents a single buildable part
+#[derive(Deserialize)]
+pub struct PartDef {
+    pub name: String,
+    pub title: String,
+    pub scene: String,
+    pub icon: String,
+    pub mass: f32,
+    pub bottom: Vec3,
+    pub collider: PartCollider,
+    pub snap: Vec<BpSnapPoint>,
+    pub solidity: GridSolidity,
+    #[serde(default)]
+    pub fuel_capacity: f32,
+    #[serde(default)]
+    pub fuel_consumption: f32,
+    #[serde(default = "default_multiplier")]
+    pub thrust_sea_level: f32,
+    #[serde(default = "default_multiplier")]
+    pub thrust_vacuum: f32,
+    #[serde(default = "default_multiplier")]
+    pub drag: f32,
+    #[serde(default)]
+    pub nose_drag: Option<f32>,
+    #[serde(default)]
+    pub torque: f32,
+}
+
+fn default_multiplier() -> f32 {
+    1.
+}
+
+// collider shape of a part, in block-local units
+#[derive(Debug, Deserialize)]
+pub enum PartCollider {
+    Cuboid { half_extents: Vec3 },
+    Cylinder { half_height: f32, radius: f32 },
+    Cone { half_height: f32, radius: f32 },
+    Ball { radius: f32 },
+}
+
+impl PartCollider {
+    fn is_valid(&self) -> bool {
+        match self {
+            PartCollider::Cuboid { half_extents } => half_extents.min_element() > 0.,
+            PartCollider::Cylinder { half_height, radius } |
+            PartCollider::Cone { half_height, radius } => *half_height > 0. && *radius > 0.,
+            PartCollider::Ball { radius } => *radius > 0.,
+        }
+    }
+
+    fn to_collider(&self) -> Collider {
+        match self {
+            PartCollider::Cuboid { half_extents } =>
+                Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            PartCollider::Cylinder { half_height, radius } => Collider::cylinder(*half_height, *radius),
+            PartCollider::Cone { half_height, radius } => Collider::cone(*half_height, *radius),
+            PartCollider::Ball { radius } => Collider::ball(*radius),
+        }
+    }
+}
+
+
+/// Possible errors that can be produced by [`PartsAssetLoader`]
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum PartsAssetLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    /// A [RON](ron) Error
+    #[error("Could not parse RON: {0}")]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    /// A part definition that parsed but is not usable
+    #[error("Invalid part definition: {0}")]
+    Invalid(String),
+}
+
+// parses and validates part definitions, leaving icon handles unset
+pub fn parse_bp_infos(bytes: &[u8]) -> Result<(BpInfos, HashMap<String,String>), PartsAssetLoaderError> {
+    let parts_def = ron::de::from_bytes::<PartsDef>(bytes)?;
+
+    let mut bps = HashMap::new();
+    let mut icon_paths = HashMap::new();
+    for part in parts_def.parts.into_iter() {
+        if bps.contains_key(&part.name) {
+            return Err(PartsAssetLoaderError::Invalid(format!("duplicate part `{}`", part.name)));
+        }
+        if part.mass <= 0. {
+            return Err(PartsAssetLoaderError::Invalid(format!("part `{}` must have positive mass", part.name)));
+        }
+        if part.fuel_capacity < 0. || part.fuel_consumption < 0. {
+            return Err(PartsAssetLoaderError::Invalid(format!("part `{}` has negative fuel values", part.name)));
+        }
+        if part.thrust_sea_level < 0. || part.thrust_vacuum < 0. {
+            return Err(PartsAssetLoaderError::Invalid(format!("part `{}` has negative thrust", part.name)));
+        }
+        if part.drag < 0. || part.nose_drag.is_some_and(|nose_drag| nose_drag < 0.) {
+            return Err(PartsAssetLoaderError::Invalid(format!("part `{}` has negative drag", part.name)));
+        }
+        if part.torque < 0. {
+            return Err(PartsAssetLoaderError::Invalid(format!("part `{}` has negative torque", part.name)));
+        }
+        if !part.collider.is_valid() {
+            return Err(PartsAssetLoaderError::Invalid(
+                format!("part `{}` has degenerate collider {:?}", part.name, part.collider)));
+        }
+        if part.snap.iter().any(|snap| snap.normal.length_squared() < 1E-6) {
+            return Err(PartsAssetLoaderError::Invalid(format!("part `{}` has snap point without normal", part.name)));
+        }
+
+        icon_paths.insert(part.name.clone(), part.icon);
+        bps.insert(part.name, BpInfo {
+            title: part.title,
+            scene: part.scene,
+            icon: Handle::default(),
+            mass: part.mass,
+            bottom: part.bottom,
+            collider: part.collider.to_collider(),
+            snap: part.snap,
+            solidity: part.solidity,
+            fuel_capacity: part.fuel_capacity,
+            fuel_consumption: part.fuel_consumption,
+            thrust_sea_level: part.thrust_sea_level,
+            thrust_vacuum: part.thrust_vacuum,
+            drag: part.drag,
+            nose_drag: part.nose_drag,
+            torque: part.torque,
+        });
+    }
+
+    if parts_def.toolbar_order.is_empty() {
+        return Err(PartsAssetLoaderError::Invalid("toolbar_order is empty".into()));
+    }
+    let mut seen = HashSet::new();
+    for name in parts_def.toolbar_order.iter() {
+        if !bps.contains_key(name) {
+            return Err(PartsAssetLoaderError::Invalid(format!("toolbar_order has unknown part `{}`", name)));
+        }
+        if !seen.insert(name) {
+            return Err(PartsAssetLoaderError::Invalid(format!("toolbar_order has duplicate part `{}`", name)));
+        }
+    }
+
+    Ok((BpInfos { bps, toolbar_order: parts_def.toolbar_order }, icon_paths))
+}
+
+#[derive(Default)]
+pub struct PartsAssetLoader;
+
+impl AssetLoader for PartsAssetLoader {
+    type Asset = PartsAsset;
+    type Settings = ();
+    type Error = PartsAssetLoaderError;
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let (mut infos, icon_paths) = parse_bp_infos(&bytes)?;
+            for (name, bp_info) in infos.bps.iter_mut() {
+                bp_info.icon = load_context.load(&icon_paths[name]);
+            }
+            Ok(PartsAsset { infos })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["parts.ron"]
+    }
+}
+
+pub struct PartsAssetLoaderPlugin;
+
+impl Plugin for PartsAssetLoaderPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_asset::<PartsAsset>()
+            .init_asset_loader::<PartsAssetLoader>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE_PART: &str = "(name: \"cube\", title: \"Cube\", scene: \"cube\", icon: \"cube.png\", mass: 1., bottom: (0., -0.5, 0.), \
+        collider: Cuboid(half_extents: (0.5, 0.5, 0.5)), snap: [], solidity: Solid)";
+
+    #[test]
+    fn building_kit_parts_are_valid() {
+        let (infos, icon_paths) = parse_bp_infos(include_bytes!("../../assets/props/building_kit.parts.ron")).unwrap();
+        assert!(!infos.toolbar_order.is_empty());
+        assert!(infos.bps.keys().all(|name| icon_paths.contains_key(name)));
+    }
+
+    #[test]
+    fn toolbar_must_list_known_parts() {
+        let parts = |toolbar: &str| format!("(parts: [{}], toolbar_order: [{}])", CUBE_PART, toolbar);
+        assert!(parse_bp_infos(parts("\"cube\"").as_bytes()).is_ok());
+        assert!(matches!(parse_bp_infos(parts("").as_bytes()), Err(PartsAssetLoaderError::Invalid(_))));
+        assert!(matches!(parse_bp_infos(parts("\"wedge\"").as_bytes()), Err(PartsAssetLoaderError::Invalid(_))));
+    }
+}
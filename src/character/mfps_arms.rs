@@ -22,6 +22,10 @@ impl Plugin for CharacterFpsArmsPlugin {
     }
 }
 
+// Tag for arms scene root, hidden in third person
+#[derive(Component)]
+pub struct MfpsArms;
+
 #[derive(Component)]
 pub struct MfpsArmsSceneHandler {
     pub names_from: Handle<Gltf>,
@@ -56,6 +60,7 @@ fn setup_mfps_arms(
     .insert(MfpsArmsSceneHandler {
         names_from: world_props.mfps_arms_handle.clone(),
     })
+    .insert(MfpsArms)
     .set_parent(camera_entity)
     ;
 
@@ -149,7 +154,7 @@ fn animate_mfps_arms(
         if building_actions.building_active {
             change_action = AnimationState::Idle;
         } else {
-            handler.active_index = (building_actions.active_index as i32).min(2);
+            handler.active_index = building_actions.active_tool.index() as i32;
             if handler.animation_state != TOOL_STATES[handler.active_index as usize] &&
             handler.animation_state != TOOL_OPEN_STATES[handler.active_index as usize] {
                 change_action = TOOL_STATES[handler.active_index as usize];
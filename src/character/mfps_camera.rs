@@ -0,0 +1,263 @@
+use crate::actions::BuildingActionsState;
+use crate::building::{Grid,GridBlock};
+use crate::character::{CharacterFpsMotionConfig,MfpsArms,MoverState};
+use crate::game_state::GameState;
+use crate::inputs::{KeyInputState,MouseCamera,MouseLookState};
+use crate::props::piloted_grid;
+use crate::world::WORLD_GROUP;
+
+use bevy::{prelude::*, input::mouse::{MouseScrollUnit,MouseWheel}};
+use bevy_rapier3d::prelude::*;
+
+// distance kept between camera and terrain or blocks in its line of sight
+const CAMERA_CLEARANCE: f32 = 0.5;
+// chase camera offsets behind and above its target
+const CHASE_DISTANCE_PLAYER: f32 = 4.;
+const CHASE_DISTANCE_GRID: f32 = 12.;
+const CHASE_HEIGHT: f32 = 0.3;
+// chase camera position smoothing rate, per second
+const CHASE_SMOOTHING: f32 = 5.;
+// horizontal speed above which a grid is chased along its velocity
+const CHASE_HEADING_SPEED: f32 = 1.;
+const ORBIT_MIN_DISTANCE: f32 = 2.;
+const ORBIT_MAX_DISTANCE: f32 = 200.;
+// relative orbit distance change per scrolled line
+const ORBIT_ZOOM_STEP: f32 = 0.1;
+// range of ray selecting grid to orbit
+const ORBIT_SELECT_DIST: f32 = 100.;
+
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum CameraMode {
+    #[default]
+    FirstPerson,
+    Chase,
+    Orbit,
+}
+
+impl CameraMode {
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::FirstPerson => CameraMode::Chase,
+            CameraMode::Chase => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::FirstPerson,
+        }
+    }
+}
+
+// system state
+#[derive(Resource)]
+pub struct CameraState {
+    pub mode: CameraMode,
+    pub orbit_distance: f32,
+    // grid orbited, player or piloted grid if none
+    orbit_target: Option<Entity>,
+    // horizontal direction chased along, kept while target stands still
+    chase_heading: Vec3,
+    // smoothed camera offset from chased target
+    chase_offset: Option<Vec3>,
+}
+
+impl Default for CameraState {
+    fn default() -> Self {
+        Self {
+            mode: CameraMode::default(),
+            orbit_distance: 10.,
+            orbit_target: None,
+            chase_heading: -Vec3::Z,
+            chase_offset: None,
+        }
+    }
+}
+
+// pulls camera position in front of an obstacle hit at toi along the ray from focus
+pub fn avoid_obstacles(
+    focus: Vec3,
+    desired: Vec3,
+    hit_toi: Option<f32>,
+) -> Vec3 {
+    let offset = desired - focus;
+    match hit_toi {
+        Some(toi) => focus + offset.normalize_or_zero() * (toi - CAMERA_CLEARANCE).clamp(0., offset.length()),
+        None => desired,
+    }
+}
+
+pub struct CharacterFpsCameraPlugin;
+
+impl Plugin for CharacterFpsCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CameraState>()
+            .add_systems(Update, (
+                update_camera_mode,
+                update_camera_sync,
+            ).chain().run_if(in_state(GameState::Running)));
+    }
+}
+
+fn update_camera_mode(
+    key_state: Res<KeyInputState>,
+    building_actions: Res<BuildingActionsState>,
+    mouse_look: Res<MouseLookState>,
+    rapier_context: Res<RapierContext>,
+    mut camera_state: ResMut<CameraState>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    camera_query: Query<&GlobalTransform, With<MouseCamera>>,
+    grids_query: Query<(), With<Grid>>,
+    mut arms_query: Query<&mut Visibility, With<MfpsArms>>,
+) {
+    // toggle view key rotates parts while building
+    if key_state.toggle_view && !building_actions.building_active {
+        camera_state.mode = camera_state.mode.next();
+        camera_state.chase_offset = None;
+        camera_state.orbit_target = None;
+
+        // orbit grid in view
+        if camera_state.mode == CameraMode::Orbit {
+            let ray_filter = QueryFilter {
+                groups: Some(CollisionGroups::new(WORLD_GROUP, WORLD_GROUP)),
+                ..default()
+            };
+            camera_state.orbit_target = camera_query.iter().next()
+                .and_then(|camera_gt| rapier_context.cast_ray(
+                    camera_gt.translation(), mouse_look.forward, ORBIT_SELECT_DIST, true, ray_filter))
+                .map(|(entity, _)| entity)
+                .filter(|entity| grids_query.contains(*entity));
+        }
+
+        let visibility = if camera_state.mode == CameraMode::FirstPerson {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        for mut arms_visibility in arms_query.iter_mut() {
+            *arms_visibility = visibility;
+        }
+    }
+
+    for mwe in mouse_wheel_events.read() {
+        if camera_state.mode != CameraMode::Orbit {
+            continue;
+        }
+        let lines = match mwe.unit {
+            MouseScrollUnit::Line => mwe.y,
+            MouseScrollUnit::Pixel => mwe.y / 16.,
+        };
+        camera_state.orbit_distance = (camera_state.orbit_distance * (1. - ORBIT_ZOOM_STEP * lines))
+            .clamp(ORBIT_MIN_DISTANCE, ORBIT_MAX_DISTANCE);
+    }
+}
+
+fn update_camera_sync(
+    time: Res<Time>,
+    mouse_look: Res<MouseLookState>,
+    rapier_context: Res<RapierContext>,
+    mut camera_state: ResMut<CameraState>,
+    mover_query: Query<(&Transform, &MoverState), (With<CharacterFpsMotionConfig>, Without<MouseCamera>)>,
+    parents: Query<&Parent>,
+    blocks_query: Query<&GridBlock>,
+    grids_query: Query<(&GlobalTransform, Option<&Velocity>, Option<&ReadMassProperties>), With<Grid>>,
+    mut query: Query<&mut Transform, With<MouseCamera>>,
+) {
+    let Ok((mover_transform, mover_state)) = mover_query.get_single() else {
+        return;
+    };
+    let mouse_forward = (mouse_look.forward * Vec3::new(1.0, 0.0, 1.0)).normalize();
+    let head_position = mover_transform.translation + 0.8 * Vec3::Y;
+
+    // grid followed by third person modes, with its center of mass
+    let target_grid = match camera_state.mode {
+        CameraMode::Orbit => camera_state.orbit_target.filter(|grid| grids_query.contains(*grid)),
+        _ => None,
+    }.or_else(|| piloted_grid(mover_state, &parents, &blocks_query));
+    let target = target_grid.and_then(|grid| grids_query.get(grid).ok().map(|(grid_gt, velocity, mass_props)| {
+        let local_com = mass_props.map_or(Vec3::ZERO, |mp| mp.local_center_of_mass);
+        (grid, grid_gt.transform_point(local_com), velocity.map_or(Vec3::ZERO, |v| v.linvel))
+    }));
+
+    let (focus, desired) = match camera_state.mode {
+        CameraMode::FirstPerson => {
+            let position = head_position + 0.15 * mouse_forward;
+            for mut camera in query.iter_mut() {
+                camera.translation = position;
+                camera.look_at(position + mouse_look.forward, Vec3::Y);
+            }
+            return;
+        }
+        CameraMode::Chase => {
+            let (focus, heading, distance) = match target {
+                Some((_, center, linvel)) => {
+                    let horizontal = linvel * Vec3::new(1.0, 0.0, 1.0);
+                    if horizontal.length() > CHASE_HEADING_SPEED {
+                        camera_state.chase_heading = horizontal.normalize();
+                    }
+                    (center, camera_state.chase_heading, CHASE_DISTANCE_GRID)
+                }
+                None => {
+                    camera_state.chase_heading = mouse_forward;
+                    (head_position, mouse_forward, CHASE_DISTANCE_PLAYER)
+                }
+            };
+            // swing behind target smoothly as its heading changes
+            let desired_offset = distance * (CHASE_HEIGHT * Vec3::Y - heading);
+            let smoothing = 1. - (-CHASE_SMOOTHING * time.delta_seconds()).exp();
+            let offset = camera_state.chase_offset
+                .map_or(desired_offset, |offset| offset.lerp(desired_offset, smoothing));
+            camera_state.chase_offset = Some(offset);
+            (focus, focus + offset)
+        }
+        CameraMode::Orbit => {
+            let focus = target.map_or(head_position, |(_, center, _)| center);
+            (focus, focus - camera_state.orbit_distance * mouse_look.forward)
+        }
+    };
+
+    // keep line of sight to focus clear of terrain and other grids
+    let mut ray_filter = QueryFilter {
+        groups: Some(CollisionGroups::new(WORLD_GROUP, WORLD_GROUP)),
+        ..default()
+    };
+    if let Some((grid, _, _)) = target {
+        ray_filter = ray_filter.exclude_collider(grid);
+    }
+    let offset = desired - focus;
+    let hit_toi = rapier_context
+        .cast_ray(focus, offset.normalize_or_zero(), offset.length() + CAMERA_CLEARANCE, true, ray_filter)
+        .map(|(_, toi)| toi);
+    let position = avoid_obstacles(focus, desired, hit_toi);
+    let look_target = if position.distance_squared(focus) > 1E-6 {
+        focus
+    } else {
+        position + mouse_look.forward
+    };
+
+    for mut camera in query.iter_mut() {
+        camera.translation = position;
+        camera.look_at(look_target, Vec3::Y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_line_of_sight_keeps_desired() {
+        let desired = Vec3::new(0., 2., -10.);
+        assert_eq!(avoid_obstacles(Vec3::ZERO, desired, None), desired);
+        let position = avoid_obstacles(Vec3::ZERO, desired, Some(desired.length() + CAMERA_CLEARANCE));
+        assert!(position.distance(desired) < 1E-5);
+        // obstacle just behind the camera still keeps its clearance
+        let position = avoid_obstacles(Vec3::ZERO, desired, Some(desired.length() + 0.2));
+        assert!((position.length() - (desired.length() + 0.2 - CAMERA_CLEARANCE)).abs() < 1E-5);
+    }
+
+    #[test]
+    fn camera_stops_short_of_obstacle() {
+        let desired = Vec3::new(0., 0., -10.);
+        let position = avoid_obstacles(Vec3::ZERO, desired, Some(4.));
+        assert!(position.distance(Vec3::new(0., 0., -4. + CAMERA_CLEARANCE)) < 1E-6);
+        // obstacle closer than the clearance keeps camera at focus
+        assert_eq!(avoid_obstacles(Vec3::ZERO, desired, Some(0.2)), Vec3::ZERO);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::GameState;
-use crate::inputs::{KeyInputState,MouseCamera,MouseLookState};
-use crate::character::CharacterFpsArmsPlugin;
+use crate::inputs::{KeyInputState,MouseLookState};
+use crate::character::{CharacterFpsArmsPlugin,CharacterFpsCameraPlugin};
 
 use bevy::prelude::*;
 use bevy::utils::HashMap;
@@ -27,6 +27,16 @@ pub struct Player;
 pub struct MoverState {
     pub seated_in_next: Option<Entity>,
     pub seated_in: Option<Entity>,
+    // noclip flight, without controller, collider or gravity
+    pub flying: bool,
+}
+
+// fly speed multiplier while running
+const FLY_RUN_FACTOR: f32 = 3.;
+
+// player body collider, removed while seated or flying
+fn player_collider() -> Collider {
+    Collider::capsule_y(0.5, 0.5)
 }
 
 
@@ -35,12 +45,13 @@ pub struct MoverState {
 impl Plugin for CharacterFpsPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins((CharacterFpsArmsPlugin::default(),));
+        app.add_plugins((CharacterFpsCameraPlugin,));
         app.add_systems(OnEnter(GameState::WorldLoading), setup_player);
         app.add_systems(Update, apply_controls.in_set(TnuaUserControlsSystemSet).run_if(in_state(GameState::Running)));
         // app.add_systems(Update, animation_patcher_system.run_if(in_state(GameState::Running)));
         // app.add_systems(Update, animate.run_if(in_state(GameState::Running)));
         app.add_systems(Update, update_mover_status.run_if(in_state(GameState::Running)));
-        app.add_systems(Update, update_camera_sync.run_if(in_state(GameState::Running)));
+        app.add_systems(Update, update_mover_flying.run_if(in_state(GameState::Running)));
     }
 }
 
@@ -68,7 +79,7 @@ fn setup_player(
     });
     
     cmd.insert(RigidBody::Dynamic);
-    cmd.insert(Collider::capsule_y(0.5, 0.5));
+    cmd.insert(player_collider());
     cmd.insert(CollisionGroups::new(CHARACTER_GROUP, CHARACTER_GROUP));
     cmd.insert(TnuaRapier3dIOBundle::default());
     cmd.insert(TnuaControllerBundle::default());
@@ -136,10 +147,8 @@ fn apply_controls(
     let mouse_forward = (mouse_look.forward * Vec3::new(1.0, 0.0, 1.0)).normalize();
     let mouse_right = (mouse_look.right * Vec3::new(1.0, 0.0, 1.0)).normalize();
     let direction = (
-        if key_state.forward { mouse_forward } else { Vec3::ZERO } +
-        if key_state.backward { -mouse_forward } else { Vec3::ZERO } +
-        if key_state.right { mouse_right } else { Vec3::ZERO } +
-        if key_state.left { -mouse_right } else { Vec3::ZERO }
+        key_state.movement.y * mouse_forward +
+        key_state.movement.x * mouse_right
     ).clamp_length_max(1.0);
 
     let jump = key_state.jump;
@@ -401,7 +410,7 @@ fn update_mover_status(
             let mut mover_cmds = commands.entity(mover_ent);
             if mover_state.seated_in.is_none() {
                 mover_cmds.insert(TnuaToggle::Enabled);
-                mover_cmds.insert(Collider::capsule_y(0.5, 0.5));
+                mover_cmds.insert(player_collider());
             } else {
                 mover_cmds.insert(TnuaToggle::Disabled);
                 mover_cmds.remove::<Collider>();
@@ -417,21 +426,61 @@ fn update_mover_status(
                         }
                     }
                 }
+            } else {
+                // seat was unbuilt
+                mover_state.seated_in_next = None;
             }
         }
     }
 }
 
-fn update_camera_sync(
+// toggles and moves the player in fly mode, along look direction
+fn update_mover_flying(
+    mut commands: Commands,
+    time: Res<Time>,
+    key_state: Res<KeyInputState>,
     mouse_look: Res<MouseLookState>,
-    mover_query: Query<(&Transform, &CharacterFpsMotionConfig), Without<MouseCamera>>,
-    mut query: Query<&mut Transform, With<MouseCamera>>,
+    mut mover_query: Query<(Entity, &mut Transform, &mut MoverState, &CharacterFpsMotionConfig, Option<&mut Velocity>)>,
 ) {
-    let (mover_transform, _mover) = mover_query.single();
-    for mut camera in query.iter_mut() {
-        let mouse_forward = (mouse_look.forward * Vec3::new(1.0, 0.0, 1.0)).normalize();
-        let mover_position = mover_transform.translation.clone() + 0.8 * Vec3::Y + 0.15 * mouse_forward;
-        camera.translation = mover_position;
-        camera.look_at(mover_position + mouse_look.forward, Vec3::Y);
+    for (mover_ent, mut mover_transform, mut mover_state, config, velocity) in mover_query.iter_mut() {
+        // seats take over the mover
+        let flying = if mover_state.seated_in.is_some() || mover_state.seated_in_next.is_some() {
+            false
+        } else if key_state.toggle_fly {
+            !mover_state.flying
+        } else {
+            mover_state.flying
+        };
+        if flying != mover_state.flying {
+            mover_state.flying = flying;
+            let mut mover_cmds = commands.entity(mover_ent);
+            if flying {
+                mover_cmds.insert(TnuaToggle::Disabled);
+                mover_cmds.insert(GravityScale(0.));
+                mover_cmds.remove::<Collider>();
+            } else {
+                mover_cmds.insert(GravityScale(1.));
+                // seat transition restores controller and collider itself
+                if mover_state.seated_in.is_none() && mover_state.seated_in_next.is_none() {
+                    mover_cmds.insert(TnuaToggle::Enabled);
+                    mover_cmds.insert(player_collider());
+                }
+            }
+        }
+        if !flying {
+            continue;
+        }
+
+        if let Some(mut velocity) = velocity {
+            *velocity = Velocity::zero();
+        }
+        let direction = (
+            key_state.movement.y * mouse_look.forward +
+            key_state.movement.x * mouse_look.right +
+            if key_state.up { Vec3::Y } else { Vec3::ZERO } +
+            if key_state.down { -Vec3::Y } else { Vec3::ZERO }
+        ).clamp_length_max(1.0);
+        let speed = if key_state.run { FLY_RUN_FACTOR * config.speed } else { config.speed };
+        mover_transform.translation += direction * speed * time.delta_seconds();
     }
 }
@@ -2,3 +2,5 @@ mod mfps_plugin;
 pub use mfps_plugin::*;
 mod mfps_arms;
 pub use mfps_arms::*;
+mod mfps_camera;
+pub use mfps_camera::*;
@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+use bevy::input::InputSystem;
+use bevy::utils::HashMap;
+use crate::inputs::{InputAction,SettingsError,read_settings,write_settings};
+use serde::{Deserialize,Serialize};
+
+const GAMEPAD_SETTINGS_NAME: &str = "gamepad_settings";
+pub const DEADZONE_RANGE: (f32, f32) = (0., 0.5);
+pub const LOOK_SENSITIVITY_RANGE: (f32, f32) = (0.5, 8.);
+
+// stick settings, fields missing from saved settings keep their defaults
+#[derive(Clone, Debug, PartialEq, Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadInputSettings {
+    // stick deflection ignored around center, as fraction of full deflection
+    pub deadzone: f32,
+    // look rotation at full stick deflection, in radians per second
+    pub look_sensitivity: f32,
+}
+
+impl Default for GamepadInputSettings {
+    fn default() -> Self {
+        Self {
+            deadzone: 0.15,
+            look_sensitivity: 2.5,
+        }
+    }
+}
+
+impl GamepadInputSettings {
+    // settings limited to supported ranges
+    pub fn clamped(&self) -> Self {
+        Self {
+            deadzone: self.deadzone.clamp(DEADZONE_RANGE.0, DEADZONE_RANGE.1),
+            look_sensitivity: self.look_sensitivity.clamp(LOOK_SENSITIVITY_RANGE.0, LOOK_SENSITIVITY_RANGE.1),
+        }
+    }
+
+    pub fn from_ron_str(ron_str: &str) -> Result<Self, SettingsError> {
+        Ok(ron::de::from_str::<GamepadInputSettings>(ron_str)?.clamped())
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, SettingsError> {
+        Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+    }
+
+    // saved settings, or defaults if none were saved yet
+    pub fn load() -> Result<Self, SettingsError> {
+        match read_settings(GAMEPAD_SETTINGS_NAME)? {
+            Some(ron_str) => Self::from_ron_str(&ron_str),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        write_settings(GAMEPAD_SETTINGS_NAME, &self.to_ron_string()?)
+    }
+}
+
+// analog state of connected gamepads, summed over all of them
+#[derive(Debug, Default, Resource)]
+pub struct GamepadInputState {
+    // left stick, x right and y forward, within unit circle
+    pub movement: Vec2,
+    // right stick, x right and y up, within unit circle
+    pub look: Vec2,
+    // right minus left trigger
+    pub throttle: f32,
+}
+
+// default button of action, triggers and sticks are mapped separately
+pub fn default_gamepad_button(action: InputAction) -> Option<GamepadButtonType> {
+    match action {
+        InputAction::Jump => Some(GamepadButtonType::South),
+        InputAction::Crouch => Some(GamepadButtonType::East),
+        InputAction::ToolUse => Some(GamepadButtonType::West),
+        InputAction::Use => Some(GamepadButtonType::North),
+        InputAction::BuildToggle => Some(GamepadButtonType::Select),
+        InputAction::Run => Some(GamepadButtonType::LeftThumb),
+        InputAction::ToggleView => Some(GamepadButtonType::RightThumb),
+        InputAction::Escape => Some(GamepadButtonType::Start),
+        InputAction::Sas => Some(GamepadButtonType::Mode),
+        InputAction::RollLeft => Some(GamepadButtonType::LeftTrigger),
+        InputAction::RollRight => Some(GamepadButtonType::RightTrigger),
+        InputAction::ToolbarPrev => Some(GamepadButtonType::DPadLeft),
+        InputAction::ToolbarNext => Some(GamepadButtonType::DPadRight),
+        InputAction::RotateY => Some(GamepadButtonType::DPadUp),
+        InputAction::RotateZ => Some(GamepadButtonType::DPadDown),
+        _ => None,
+    }
+}
+
+// maintains mappings from gamepad buttons to the same game-logic actions as keys
+#[derive(Clone, Debug, Resource)]
+pub struct GamepadInputMap {
+    bindings: HashMap<InputAction, GamepadButtonType>,
+}
+
+impl Default for GamepadInputMap {
+    fn default() -> Self {
+        Self {
+            bindings: InputAction::ALL.iter()
+                .filter_map(|action| default_gamepad_button(*action).map(|button| (*action, button)))
+                .collect(),
+        }
+    }
+}
+
+impl GamepadInputMap {
+    pub fn button(&self, action: InputAction) -> Option<GamepadButtonType> {
+        self.bindings.get(&action).copied()
+    }
+
+    // action button held on any connected gamepad
+    pub fn pressed(&self, gamepads: &Gamepads, gamepad_buttons: &Input<GamepadButton>, action: InputAction) -> bool {
+        self.button(action).is_some_and(|button_type| gamepads.iter()
+            .any(|gamepad| gamepad_buttons.pressed(GamepadButton::new(gamepad, button_type))))
+    }
+}
+
+// rescales stick deflection beyond the deadzone to 0..1, keeping its direction
+pub fn apply_deadzone(stick: Vec2, deadzone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= deadzone || deadzone >= 1. {
+        return Vec2::ZERO;
+    }
+    stick * ((length.min(1.) - deadzone) / (1. - deadzone)) / length
+}
+
+/// Reads sticks and triggers of connected gamepads, buttons are read with keys as actions
+pub struct GamepadInputPlugin;
+
+impl Plugin for GamepadInputPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = GamepadInputSettings::load().unwrap_or_else(|err| {
+            error!("Failed to load gamepad settings: {}", err);
+            GamepadInputSettings::default()
+        });
+        app.insert_resource(settings);
+        app.init_resource::<GamepadInputState>();
+        app.init_resource::<GamepadInputMap>();
+        app.add_systems(PreUpdate, update_gamepad_input.after(InputSystem));
+    }
+}
+
+fn update_gamepad_input(
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    button_axes: Res<Axis<GamepadButton>>,
+    settings: Res<GamepadInputSettings>,
+    mut state: ResMut<GamepadInputState>,
+) {
+    let mut movement = Vec2::ZERO;
+    let mut look = Vec2::ZERO;
+    let mut throttle = 0.;
+    for gamepad in gamepads.iter() {
+        let axis = |axis_type| axes.get(GamepadAxis::new(gamepad, axis_type)).unwrap_or(0.);
+        let trigger = |button_type| button_axes.get(GamepadButton::new(gamepad, button_type)).unwrap_or(0.);
+        movement += apply_deadzone(Vec2::new(
+            axis(GamepadAxisType::LeftStickX), axis(GamepadAxisType::LeftStickY)), settings.deadzone);
+        look += apply_deadzone(Vec2::new(
+            axis(GamepadAxisType::RightStickX), axis(GamepadAxisType::RightStickY)), settings.deadzone);
+        throttle += trigger(GamepadButtonType::RightTrigger2) - trigger(GamepadButtonType::LeftTrigger2);
+    }
+
+    state.movement = movement.clamp_length_max(1.);
+    state.look = look.clamp_length_max(1.);
+    state.throttle = throttle.clamp(-1., 1.);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadzone_rescales_deflection() {
+        assert_eq!(apply_deadzone(Vec2::new(0.1, 0.), 0.2), Vec2::ZERO);
+        let half = apply_deadzone(Vec2::new(0., 0.6), 0.2);
+        assert!(half.distance(Vec2::new(0., 0.5)) < 1E-6);
+        // full deflection reaches full length, beyond it is limited
+        assert!((apply_deadzone(Vec2::new(1., 1.), 0.2).length() - 1.).abs() < 1E-6);
+        assert_eq!(apply_deadzone(Vec2::X, 1.), Vec2::ZERO);
+    }
+
+    #[test]
+    fn saved_settings_are_clamped() {
+        let settings = GamepadInputSettings::from_ron_str("(deadzone: 0.9)").unwrap();
+        assert_eq!(settings.deadzone, DEADZONE_RANGE.1);
+        assert_eq!(settings.look_sensitivity, GamepadInputSettings::default().look_sensitivity);
+        let saved = settings.to_ron_string().unwrap();
+        assert_eq!(GamepadInputSettings::from_ron_str(&saved).unwrap(), settings);
+    }
+}
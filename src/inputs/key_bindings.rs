@@ -0,0 +1,430 @@
+use bevy::prelude::*;
+use crate::inputs::{SettingsError,read_settings,write_settings};
+use serde::{Deserialize,Serialize};
+use std::collections::BTreeMap;
+
+const KEY_BINDINGS_NAME: &str = "key_bindings";
+
+// actions of the same group are active at the same time and can't share key bindings,
+// building keys take precedence over general ones while building
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputGroup {
+    General,
+    Building,
+}
+
+// modifier held for a key chord, either side
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyModifier {
+    Ctrl,
+    Shift,
+    Alt,
+}
+
+impl KeyModifier {
+    pub const ALL: [KeyModifier; 3] = [KeyModifier::Ctrl, KeyModifier::Shift, KeyModifier::Alt];
+
+    pub fn keys(self) -> [KeyCode; 2] {
+        match self {
+            KeyModifier::Ctrl => [KeyCode::ControlLeft, KeyCode::ControlRight],
+            KeyModifier::Shift => [KeyCode::ShiftLeft, KeyCode::ShiftRight],
+            KeyModifier::Alt => [KeyCode::AltLeft, KeyCode::AltRight],
+        }
+    }
+
+    // modifier key is part of
+    pub fn of_key(key: KeyCode) -> Option<KeyModifier> {
+        KeyModifier::ALL.iter().copied().find(|modifier| modifier.keys().contains(&key))
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            KeyModifier::Ctrl => "Ctrl",
+            KeyModifier::Shift => "Shift",
+            KeyModifier::Alt => "Alt",
+        }
+    }
+}
+
+// key bound to an action, optionally only with a modifier held
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyBinding {
+    pub key: KeyCode,
+    #[serde(default)]
+    pub modifier: Option<KeyModifier>,
+}
+
+impl KeyBinding {
+    pub fn key(key: KeyCode) -> Self {
+        Self { key, modifier: None }
+    }
+
+    pub fn chord(modifier: KeyModifier, key: KeyCode) -> Self {
+        Self { key, modifier: Some(modifier) }
+    }
+
+    // keys held for the binding, key and modifier
+    pub fn uses_key(&self, key: KeyCode) -> bool {
+        self.key == key || self.modifier.is_some_and(|modifier| modifier.keys().contains(&key))
+    }
+
+    pub fn held(&self, keyboard_input: &Input<KeyCode>) -> bool {
+        keyboard_input.pressed(self.key) &&
+            self.modifier.is_none_or(|modifier| keyboard_input.any_pressed(modifier.keys()))
+    }
+
+    pub fn label(&self) -> String {
+        match self.modifier {
+            Some(modifier) => format!("{}+{}", modifier.label(), key_label(self.key)),
+            None => key_label(self.key),
+        }
+    }
+}
+
+// game-logic action bound to a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum InputAction {
+    Forward,
+    Backward,
+    Left,
+    Right,
+    Jump,
+    Run,
+    Crouch,
+    Fly,
+    ToggleView,
+    Use,
+    ToolUse,
+    Escape,
+    RollLeft,
+    RollRight,
+    Dampening,
+    Sas,
+    Record,
+    ThrustersUp,
+    ThrustersDown,
+    BuildToggle,
+    BuildExit,
+    Toolbar1,
+    Toolbar2,
+    Toolbar3,
+    Toolbar4,
+    Toolbar5,
+    Toolbar6,
+    Toolbar7,
+    ToolbarPrev,
+    ToolbarNext,
+    Quicksave,
+    Quickload,
+    RotateY,
+    RotateZ,
+    Symmetry,
+    StageNext,
+    StagePrev,
+    Undo,
+    Redo,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 39] = [
+        InputAction::Forward, InputAction::Backward, InputAction::Left, InputAction::Right,
+        InputAction::Jump, InputAction::Run, InputAction::Crouch, InputAction::Fly,
+        InputAction::ToggleView, InputAction::Use, InputAction::ToolUse, InputAction::Escape,
+        InputAction::RollLeft, InputAction::RollRight, InputAction::Dampening, InputAction::Sas,
+        InputAction::Record, InputAction::ThrustersUp, InputAction::ThrustersDown,
+        InputAction::BuildToggle, InputAction::BuildExit,
+        InputAction::Toolbar1, InputAction::Toolbar2, InputAction::Toolbar3, InputAction::Toolbar4,
+        InputAction::Toolbar5, InputAction::Toolbar6, InputAction::Toolbar7,
+        InputAction::ToolbarPrev, InputAction::ToolbarNext,
+        InputAction::Quicksave, InputAction::Quickload,
+        InputAction::RotateY, InputAction::RotateZ, InputAction::Symmetry,
+        InputAction::StageNext, InputAction::StagePrev,
+        InputAction::Undo, InputAction::Redo,
+    ];
+
+    // toolbar slot actions, in slot order
+    pub const TOOLBAR: [InputAction; 7] = [
+        InputAction::Toolbar1, InputAction::Toolbar2, InputAction::Toolbar3, InputAction::Toolbar4,
+        InputAction::Toolbar5, InputAction::Toolbar6, InputAction::Toolbar7,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            InputAction::Forward => "Forward / Pitch down",
+            InputAction::Backward => "Backward / Pitch up",
+            InputAction::Left => "Left / Yaw left",
+            InputAction::Right => "Right / Yaw right",
+            InputAction::Jump => "Jump / Up / Stage",
+            InputAction::Run => "Run / Throttle up",
+            InputAction::Crouch => "Crouch / Down / Throttle down",
+            InputAction::Fly => "Fly",
+            InputAction::ToggleView => "Camera view",
+            InputAction::Use => "Use",
+            InputAction::ToolUse => "Use tool",
+            InputAction::Escape => "Pause",
+            InputAction::RollLeft => "Roll left",
+            InputAction::RollRight => "Roll right",
+            InputAction::Dampening => "Dampening",
+            InputAction::Sas => "Attitude hold",
+            InputAction::Record => "Flight recorder",
+            InputAction::ThrustersUp => "Thrusters power up",
+            InputAction::ThrustersDown => "Thrusters power down",
+            InputAction::BuildToggle => "Building tool",
+            InputAction::BuildExit => "Put away tool",
+            InputAction::Toolbar1 => "Toolbar 1",
+            InputAction::Toolbar2 => "Toolbar 2",
+            InputAction::Toolbar3 => "Toolbar 3",
+            InputAction::Toolbar4 => "Toolbar 4",
+            InputAction::Toolbar5 => "Toolbar 5",
+            InputAction::Toolbar6 => "Toolbar 6",
+            InputAction::Toolbar7 => "Toolbar 7",
+            InputAction::ToolbarPrev => "Previous toolbar item",
+            InputAction::ToolbarNext => "Next toolbar item",
+            InputAction::Quicksave => "Quicksave blueprint",
+            InputAction::Quickload => "Quickload blueprint",
+            InputAction::RotateY => "Rotate part (Y)",
+            InputAction::RotateZ => "Rotate part (Z)",
+            InputAction::Symmetry => "Symmetry",
+            InputAction::StageNext => "Next stage",
+            InputAction::StagePrev => "Previous stage",
+            InputAction::Undo => "Undo",
+            InputAction::Redo => "Redo",
+        }
+    }
+
+    pub fn group(self) -> InputGroup {
+        match self {
+            InputAction::RotateY | InputAction::RotateZ | InputAction::Symmetry |
+            InputAction::StageNext | InputAction::StagePrev |
+            InputAction::Quicksave | InputAction::Quickload => InputGroup::Building,
+            _ => InputGroup::General,
+        }
+    }
+
+    pub fn default_binding(self) -> KeyBinding {
+        match self {
+            InputAction::Undo | InputAction::Redo => KeyBinding::chord(KeyModifier::Ctrl, self.default_key()),
+            _ => KeyBinding::key(self.default_key()),
+        }
+    }
+
+    fn default_key(self) -> KeyCode {
+        match self {
+            InputAction::Forward => KeyCode::W,
+            InputAction::Backward => KeyCode::S,
+            InputAction::Left => KeyCode::A,
+            InputAction::Right => KeyCode::D,
+            InputAction::Jump => KeyCode::Space,
+            InputAction::Run => KeyCode::ShiftLeft,
+            #[cfg(target_arch = "wasm32")]
+            InputAction::Crouch => KeyCode::AltLeft,
+            #[cfg(not(target_arch = "wasm32"))]
+            InputAction::Crouch => KeyCode::ControlLeft,
+            InputAction::Fly => KeyCode::N,
+            InputAction::ToggleView => KeyCode::T,
+            InputAction::Use => KeyCode::F,
+            InputAction::ToolUse => KeyCode::Return,
+            InputAction::Escape => KeyCode::Escape,
+            InputAction::RollLeft => KeyCode::Q,
+            InputAction::RollRight => KeyCode::E,
+            InputAction::Dampening => KeyCode::V,
+            InputAction::Sas => KeyCode::G,
+            InputAction::Record => KeyCode::L,
+            InputAction::ThrustersUp => KeyCode::Z,
+            InputAction::ThrustersDown => KeyCode::X,
+            InputAction::BuildToggle => KeyCode::B,
+            InputAction::BuildExit => KeyCode::H,
+            InputAction::Toolbar1 => KeyCode::Key1,
+            InputAction::Toolbar2 => KeyCode::Key2,
+            InputAction::Toolbar3 => KeyCode::Key3,
+            InputAction::Toolbar4 => KeyCode::Key4,
+            InputAction::Toolbar5 => KeyCode::Key5,
+            InputAction::Toolbar6 => KeyCode::Key6,
+            InputAction::Toolbar7 => KeyCode::Key7,
+            InputAction::ToolbarPrev => KeyCode::Comma,
+            InputAction::ToolbarNext => KeyCode::Period,
+            InputAction::Quicksave => KeyCode::F5,
+            InputAction::Quickload => KeyCode::F9,
+            InputAction::RotateY => KeyCode::T,
+            InputAction::RotateZ => KeyCode::R,
+            InputAction::Symmetry => KeyCode::M,
+            InputAction::StageNext => KeyCode::BracketRight,
+            InputAction::StagePrev => KeyCode::BracketLeft,
+            InputAction::Undo => KeyCode::Z,
+            InputAction::Redo => KeyCode::Y,
+        }
+    }
+}
+
+// short display name of key
+pub fn key_label(key: KeyCode) -> String {
+    let label = format!("{:?}", key);
+    match key {
+        KeyCode::BracketLeft => "[".into(),
+        KeyCode::BracketRight => "]".into(),
+        KeyCode::Comma => ",".into(),
+        KeyCode::Period => ".".into(),
+        KeyCode::ShiftLeft => "LShift".into(),
+        KeyCode::ShiftRight => "RShift".into(),
+        KeyCode::ControlLeft => "LCtrl".into(),
+        KeyCode::ControlRight => "RCtrl".into(),
+        KeyCode::AltLeft => "LAlt".into(),
+        KeyCode::AltRight => "RAlt".into(),
+        KeyCode::Escape => "Esc".into(),
+        _ => label.strip_prefix("Key").filter(|digit| digit.len() == 1)
+            .map_or(label.clone(), |digit| digit.into()),
+    }
+}
+
+// maintains mappings from input actions to game-logic actions
+#[derive(Clone, Debug, PartialEq, Resource, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct KeyInputMap {
+    bindings: BTreeMap<InputAction, KeyBinding>,
+}
+
+impl Default for KeyInputMap {
+    fn default() -> Self {
+        Self {
+            bindings: InputAction::ALL.iter().map(|action| (*action, action.default_binding())).collect(),
+        }
+    }
+}
+
+impl KeyInputMap {
+    pub fn binding(&self, action: InputAction) -> KeyBinding {
+        self.bindings.get(&action).copied().unwrap_or_else(|| action.default_binding())
+    }
+
+    // keys of a held chord belong to it, so ctrl+z neither crouches on ctrl nor fires what z alone is bound to.
+    // while building, general actions sharing a binding with a building action are masked
+    pub fn pressed(&self, keyboard_input: &Input<KeyCode>, action: InputAction, building_active: bool) -> bool {
+        let binding = self.binding(action);
+        if !binding.held(keyboard_input) {
+            return false;
+        }
+        if building_active && action.group() == InputGroup::General && InputAction::ALL.iter()
+            .any(|other| other.group() == InputGroup::Building && self.binding(*other) == binding) {
+            return false;
+        }
+        binding.modifier.is_some() || !InputAction::ALL.iter()
+            .map(|other| self.binding(*other))
+            .any(|chord| chord.modifier.is_some() && chord.held(keyboard_input) && chord.uses_key(binding.key))
+    }
+
+    // action of the same group already bound to the same key and modifier
+    pub fn conflict(&self, action: InputAction, binding: KeyBinding) -> Option<InputAction> {
+        InputAction::ALL.iter().copied()
+            .find(|other| *other != action && other.group() == action.group() && self.binding(*other) == binding)
+    }
+
+    // binds key to action, swapping bindings with a conflicting action, which is returned
+    pub fn bind(&mut self, action: InputAction, binding: KeyBinding) -> Option<InputAction> {
+        let conflict = self.conflict(action, binding);
+        if let Some(other) = conflict {
+            self.bindings.insert(other, self.binding(action));
+        }
+        self.bindings.insert(action, binding);
+        conflict
+    }
+
+    // parses bindings, actions missing from the file keep their defaults.
+    // older files bound plain keys, their chords keep the default modifier
+    pub fn from_ron_str(ron_str: &str) -> Result<Self, SettingsError> {
+        let mut map = KeyInputMap::default();
+        match ron::de::from_str::<KeyInputMap>(ron_str) {
+            Ok(parsed) => map.bindings.extend(parsed.bindings),
+            Err(err) => {
+                let keys = ron::de::from_str::<BTreeMap<InputAction, KeyCode>>(ron_str).map_err(|_| err)?;
+                map.bindings.extend(keys.into_iter().map(|(action, key)|
+                    (action, KeyBinding { key, ..action.default_binding() })));
+            }
+        }
+        Ok(map)
+    }
+    pub fn to_ron_string(&self) -> Result<String, SettingsError> {
+        Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+    }
+
+    // saved bindings, or defaults if none were saved yet
+    pub fn load() -> Result<Self, SettingsError> {
+        match read_settings(KEY_BINDINGS_NAME)? {
+            Some(ron_str) => Self::from_ron_str(&ron_str),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        write_settings(KEY_BINDINGS_NAME, &self.to_ron_string()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys_held(keys: &[KeyCode]) -> Input<KeyCode> {
+        let mut keyboard_input = Input::default();
+        for key in keys {
+            keyboard_input.press(*key);
+        }
+        keyboard_input
+    }
+
+    #[test]
+    fn chord_takes_its_keys() {
+        let map = KeyInputMap::default();
+        let ctrl_z = keys_held(&[KeyCode::ControlLeft, KeyCode::Z]);
+        assert!(map.pressed(&ctrl_z, InputAction::Undo, false));
+        assert!(!map.pressed(&ctrl_z, InputAction::ThrustersUp, false));
+        assert!(!map.pressed(&ctrl_z, InputAction::Crouch, false));
+
+        let z = keys_held(&[KeyCode::Z]);
+        assert!(!map.pressed(&z, InputAction::Undo, false));
+        assert!(map.pressed(&z, InputAction::ThrustersUp, false));
+        // other keys still combine with a held modifier
+        let crouch_walk = keys_held(&[KeyCode::ControlLeft, KeyCode::W]);
+        assert!(map.pressed(&crouch_walk, InputAction::Crouch, false));
+        assert!(map.pressed(&crouch_walk, InputAction::Forward, false));
+    }
+
+    #[test]
+    fn building_keys_take_precedence_while_building() {
+        let mut map = KeyInputMap::default();
+        // toggle view and rotate share t by default
+        let t = keys_held(&[KeyCode::T]);
+        assert!(map.pressed(&t, InputAction::ToggleView, false));
+        assert!(map.pressed(&t, InputAction::RotateY, false));
+        assert!(!map.pressed(&t, InputAction::ToggleView, true));
+        assert!(map.pressed(&t, InputAction::RotateY, true));
+
+        // cross-group duplicates aren't conflicts, general keys not used by building stay active
+        assert_eq!(map.bind(InputAction::Jump, KeyBinding::key(KeyCode::M)), None);
+        let m = keys_held(&[KeyCode::M]);
+        assert!(!map.pressed(&m, InputAction::Jump, true));
+        assert!(map.pressed(&m, InputAction::Symmetry, true));
+        let w = keys_held(&[KeyCode::W]);
+        assert!(map.pressed(&w, InputAction::Forward, true));
+    }
+
+    #[test]
+    fn conflicts_compare_modifiers() {
+        let mut map = KeyInputMap::default();
+        assert_eq!(map.conflict(InputAction::Undo, KeyBinding::key(KeyCode::Z)), Some(InputAction::ThrustersUp));
+        assert_eq!(map.conflict(InputAction::Sas, KeyBinding::chord(KeyModifier::Ctrl, KeyCode::Z)), Some(InputAction::Undo));
+        assert_eq!(map.conflict(InputAction::Sas, KeyBinding::chord(KeyModifier::Shift, KeyCode::Z)), None);
+
+        assert_eq!(map.bind(InputAction::Redo, KeyBinding::chord(KeyModifier::Ctrl, KeyCode::Z)), Some(InputAction::Undo));
+        assert_eq!(map.binding(InputAction::Undo), KeyBinding::chord(KeyModifier::Ctrl, KeyCode::Y));
+    }
+
+    #[test]
+    fn plain_key_files_keep_default_modifiers() {
+        let map = KeyInputMap::from_ron_str("{Undo: U, Jump: J}").unwrap();
+        assert_eq!(map.binding(InputAction::Undo), KeyBinding::chord(KeyModifier::Ctrl, KeyCode::U));
+        assert_eq!(map.binding(InputAction::Jump), KeyBinding::key(KeyCode::J));
+
+        let saved = KeyInputMap::from_ron_str(&map.to_ron_string().unwrap()).unwrap();
+        assert_eq!(saved, map);
+    }
+}
@@ -1,51 +1,17 @@
 use bevy::prelude::*;
+use bevy::input::InputSystem;
+use crate::actions::BuildingActionsState;
 use crate::game_state::GameState;
-use crate::inputs::CursorLockState;
-
-// maintains mappings from input actions to game-logic actions
-#[derive(Debug, Resource)]
-pub struct KeyInputMap {
-    pub key_forward: KeyCode,
-    pub key_backward: KeyCode,
-    pub key_left: KeyCode,
-    pub key_right: KeyCode,
-    pub key_jump: KeyCode,
-    pub key_run: KeyCode,
-    pub key_crouch: KeyCode,
-    pub key_fly: KeyCode,
-    pub key_toggleview: KeyCode,
-    pub key_action_use: KeyCode,
-    pub key_escape: KeyCode,
-}
-
-impl Default for KeyInputMap {
-    fn default() -> Self {
-        Self {
-            key_forward: KeyCode::W,
-            key_backward: KeyCode::S,
-            key_left: KeyCode::A,
-            key_right: KeyCode::D,
-            key_jump: KeyCode::Space,
-            key_run: KeyCode::ShiftLeft,
-            #[cfg(target_arch = "wasm32")]
-            key_crouch: KeyCode::AltLeft,
-            #[cfg(not(target_arch = "wasm32"))]
-            key_crouch: KeyCode::ControlLeft,
-            key_fly: KeyCode::N,
-            key_toggleview: KeyCode::T,
-            key_action_use: KeyCode::F,
-            key_escape: KeyCode::Escape,
-        }
-    }
-}
+use crate::inputs::{CursorLockState,GamepadInputMap,GamepadInputState,InputAction,KeyInputMap,
+    TouchInputState};
 
 // maintains per-frame state of mapped activations from input to game-logic actions
 #[derive(Debug, Default, Resource)]
 pub struct KeyInputState {
-    pub forward: bool,
-    pub backward: bool,
-    pub left: bool,
-    pub right: bool,
+    // x right and y forward, within unit circle
+    pub movement: Vec2,
+    // -1 to 1, down to up
+    pub throttle: f32,
     pub run: bool,
     pub crouch: bool,
     pub up: bool,
@@ -54,6 +20,13 @@ pub struct KeyInputState {
     pub action_use: bool,
     pub toggle_fly: bool,
     pub toggle_view: bool,
+    pub undo: bool,
+    pub redo: bool,
+    pub roll_left: bool,
+    pub roll_right: bool,
+    pub toggle_dampening: bool,
+    pub toggle_sas: bool,
+    pub toggle_record: bool,
 }
 
 // Plugin for keyboard input systems
@@ -62,38 +35,85 @@ pub struct KeyInputPlugin;
 
 impl Plugin for KeyInputPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<KeyInputMap>();
+        let input_map = KeyInputMap::load().unwrap_or_else(|err| {
+            error!("Failed to load key bindings: {}", err);
+            KeyInputMap::default()
+        });
+        app.insert_resource(input_map);
+        app.init_resource::<Input<InputAction>>();
         app.init_resource::<KeyInputState>();
+        app.add_systems(PreUpdate, input_to_actions.after(InputSystem));
         app.add_systems(Update, 
             input_to_move.run_if(in_state(GameState::Running)));
     }
 }
 
-// updates desired move velocity from keyboard input
-pub fn input_to_move(
+// presses actions bound to held keys, gamepad buttons or on-screen buttons
+pub fn input_to_actions(
     keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
     input_map: Res<KeyInputMap>,
+    gamepad_map: Res<GamepadInputMap>,
+    touch_state: Res<TouchInputState>,
+    building_actions: Option<Res<BuildingActionsState>>,
+    mut action_input: ResMut<Input<InputAction>>,
+) {
+    action_input.clear();
+    let building_active = building_actions.is_some_and(|building_actions| building_actions.building_active);
+    for action in InputAction::ALL.iter().copied() {
+        if input_map.pressed(&keyboard_input, action, building_active) ||
+            gamepad_map.pressed(&gamepads, &gamepad_buttons, action) ||
+            touch_state.pressed(action) {
+            action_input.press(action);
+        } else {
+            action_input.release(action);
+        }
+    }
+}
+
+// updates desired move velocity from action input
+pub fn input_to_move(
+    action_input: Res<Input<InputAction>>,
+    gamepad_state: Res<GamepadInputState>,
+    touch_state: Res<TouchInputState>,
     mut state: ResMut<KeyInputState>,
     cursor_lock: Res<CursorLockState>,
     mut game_state: ResMut<NextState<GameState>>,
 ) {
+    let pressed = |action| cursor_lock.enabled && action_input.pressed(action);
+    let just_pressed = |action| cursor_lock.enabled && action_input.just_pressed(action);
+
     // check esc
-    if cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_escape) {
+    if just_pressed(InputAction::Escape) {
         game_state.set(GameState::Paused);
     }
 
     // update input state from key states
-    state.run = cursor_lock.enabled && keyboard_input.pressed(input_map.key_run);
-    state.toggle_fly = cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_fly);
-    state.toggle_view = cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_toggleview);
-    state.jump = cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_jump);
-    state.action_use = cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_action_use);
+    state.run = pressed(InputAction::Run);
+    state.toggle_fly = just_pressed(InputAction::Fly);
+    state.toggle_view = just_pressed(InputAction::ToggleView);
+    state.jump = just_pressed(InputAction::Jump);
+    state.action_use = just_pressed(InputAction::Use);
+    state.toggle_dampening = just_pressed(InputAction::Dampening);
+    state.toggle_sas = just_pressed(InputAction::Sas);
+    state.toggle_record = just_pressed(InputAction::Record);
+
+    state.undo = just_pressed(InputAction::Undo);
+    state.redo = just_pressed(InputAction::Redo);
 
-    // update desired velocity from key states
-    state.forward = cursor_lock.enabled && keyboard_input.pressed(input_map.key_forward);
-    state.backward = cursor_lock.enabled && keyboard_input.pressed(input_map.key_backward);
-    state.right = cursor_lock.enabled && keyboard_input.pressed(input_map.key_right);
-    state.left = cursor_lock.enabled && keyboard_input.pressed(input_map.key_left);
-    state.up = cursor_lock.enabled && keyboard_input.pressed(input_map.key_jump);
-    state.down = cursor_lock.enabled && keyboard_input.pressed(input_map.key_crouch);
+    // update desired velocity from key states, gamepad sticks and touch joystick
+    let axis = |positive, negative| (pressed(positive) as i32 - pressed(negative) as i32) as f32;
+    let (stick, trigger) = if cursor_lock.enabled {
+        (gamepad_state.movement + touch_state.movement, gamepad_state.throttle + touch_state.throttle)
+    } else {
+        (Vec2::ZERO, 0.)
+    };
+    state.movement = (Vec2::new(axis(InputAction::Right, InputAction::Left),
+        axis(InputAction::Forward, InputAction::Backward)) + stick).clamp_length_max(1.);
+    state.throttle = (axis(InputAction::Run, InputAction::Crouch) + trigger).clamp(-1., 1.);
+    state.up = pressed(InputAction::Jump);
+    state.down = pressed(InputAction::Crouch);
+    state.roll_left = pressed(InputAction::RollLeft);
+    state.roll_right = pressed(InputAction::RollRight);
 }
@@ -1,4 +1,12 @@
+mod gamepad_input;
+pub use gamepad_input::*;
+mod key_bindings;
+pub use key_bindings::*;
 mod key_input;
 pub use key_input::*;
 mod mouse_input;
 pub use mouse_input::*;
+mod settings_storage;
+pub use settings_storage::*;
+mod touch_input;
+pub use touch_input::*;
@@ -2,11 +2,27 @@ use bevy::{prelude::*, input::mouse::{MouseMotion,MouseWheel}, window::CursorGra
 #[cfg(target_arch = "wasm32")]
 use web_sys;
 use crate::game_state::GameState;
+use crate::inputs::{GamepadInputSettings,GamepadInputState,SettingsError,TouchInputState,
+    read_settings,write_settings};
+use serde::{Deserialize,Serialize};
 
-#[derive(Resource)]
+const MOUSE_SETTINGS_NAME: &str = "mouse_settings";
+pub const SENSITIVITY_RANGE: (f32, f32) = (0.0002, 0.01);
+pub const FOV_RANGE: (f32, f32) = (30., 120.);
+pub const SMOOTHING_RANGE: (f32, f32) = (0., 0.3);
+
+// look settings, fields missing from saved settings keep their defaults
+#[derive(Clone, Debug, PartialEq, Resource, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MouseSettings {
+    // look rotation per pixel of mouse motion, in radians
     pub sensitivity: f32,
     pub zoom_sensitivity: f32,
+    pub invert_y: bool,
+    // vertical field of view, in degrees
+    pub fov: f32,
+    // time constant of look smoothing in seconds, 0 for none
+    pub smoothing: f32,
 }
 
 impl Default for MouseSettings {
@@ -14,8 +30,51 @@ impl Default for MouseSettings {
         Self {
             sensitivity: 0.002,
             zoom_sensitivity: 0.02,
+            invert_y: false,
+            fov: 45.,
+            smoothing: 0.,
+        }
+    }
+}
+
+impl MouseSettings {
+    // settings limited to supported ranges
+    pub fn clamped(&self) -> Self {
+        Self {
+            sensitivity: self.sensitivity.clamp(SENSITIVITY_RANGE.0, SENSITIVITY_RANGE.1),
+            fov: self.fov.clamp(FOV_RANGE.0, FOV_RANGE.1),
+            smoothing: self.smoothing.clamp(SMOOTHING_RANGE.0, SMOOTHING_RANGE.1),
+            ..self.clone()
+        }
+    }
+
+    pub fn from_ron_str(ron_str: &str) -> Result<Self, SettingsError> {
+        Ok(ron::de::from_str::<MouseSettings>(ron_str)?.clamped())
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, SettingsError> {
+        Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+    }
+
+    // saved settings, or defaults if none were saved yet
+    pub fn load() -> Result<Self, SettingsError> {
+        match read_settings(MOUSE_SETTINGS_NAME)? {
+            Some(ron_str) => Self::from_ron_str(&ron_str),
+            None => Ok(Self::default()),
         }
     }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        write_settings(MOUSE_SETTINGS_NAME, &self.to_ron_string()?)
+    }
+}
+
+// fraction of pending look rotation applied over dt, all of it without smoothing
+pub fn look_smoothing_factor(smoothing: f32, dt: f32) -> f32 {
+    if smoothing <= 0. {
+        return 1.;
+    }
+    1. - (-dt / smoothing).exp()
 }
 
 const PITCH_BOUND: f32 = std::f32::consts::FRAC_PI_2 - 1E-3;
@@ -57,12 +116,17 @@ impl Plugin for MouseInputPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<CursorLockState>();
         app.init_resource::<MouseLookState>();
-        app.init_resource::<MouseSettings>();
+        let settings = MouseSettings::load().unwrap_or_else(|err| {
+            error!("Failed to load mouse settings: {}", err);
+            MouseSettings::default()
+        });
+        app.insert_resource(settings);
 
         app.add_systems(OnEnter(GameState::WorldLoading), setup_mouse_inputs);
         app.add_systems(Update, update_cursor_lock.run_if(in_state(GameState::Running)));
         app.add_systems(Update, update_cursor_lock_wasm_running.run_if(in_state(GameState::Running)));
         app.add_systems(Update, input_to_look.run_if(in_state(GameState::Running)));
+        app.add_systems(Update, update_camera_fov);
         app.add_systems(Update, update_cursor_lock_wasm_paused.run_if(in_state(GameState::Paused)));
     }
 }
@@ -131,20 +195,38 @@ pub fn update_cursor_lock(
 }
 
 pub fn input_to_look(
+    time: Res<Time>,
     mut mouse_motion_events: EventReader<MouseMotion>,
     mut mouse_look: ResMut<MouseLookState>,
     settings: Res<MouseSettings>,
+    gamepad_settings: Res<GamepadInputSettings>,
+    gamepad_state: Res<GamepadInputState>,
+    touch_state: Res<TouchInputState>,
     cursor_lock: Res<CursorLockState>,
+    mut pending: Local<Vec2>,
 ) {
-    let mut delta = Vec2::ZERO;
+    // dragging turns like moving the mouse
+    let mut delta = -touch_state.look_delta;
     for motion in mouse_motion_events.read() {
         delta -= motion.delta;
     }
     if !cursor_lock.enabled {
+        *pending = Vec2::ZERO;
         return;
     }
-    if delta.length_squared() > 1E-6 {
-        delta *= settings.sensitivity;
+    delta *= settings.sensitivity;
+    if settings.invert_y {
+        delta.y = -delta.y;
+    }
+    // right stick turns at a rate independent of frame time
+    let stick = gamepad_state.look * gamepad_settings.look_sensitivity * time.delta_seconds();
+    delta += Vec2::new(-stick.x, stick.y);
+
+    // smoothing spreads look rotation over the following frames, without dropping any
+    *pending += delta;
+    let delta = *pending * look_smoothing_factor(settings.smoothing, time.delta_seconds());
+    *pending -= delta;
+    if delta.length_squared() > 1E-12 {
         mouse_look.yaw_pitch_roll += delta.extend(0.0);
         if mouse_look.yaw_pitch_roll.y > PITCH_BOUND {
             mouse_look.yaw_pitch_roll.y = PITCH_BOUND;
@@ -170,3 +252,19 @@ pub fn input_to_look(
         mouse_look.up = rotation * Vec3::Y;
     }
 }
+
+// applies field of view setting to 3d cameras
+fn update_camera_fov(
+    settings: Res<MouseSettings>,
+    mut cameras: Query<&mut Projection, With<Camera3d>>,
+) {
+    let fov = settings.fov.to_radians();
+    for mut projection in cameras.iter_mut() {
+        let Projection::Perspective(perspective) = projection.as_ref() else {
+            continue;
+        };
+        if (perspective.fov - fov).abs() > 1E-6 {
+            *projection = Projection::Perspective(PerspectiveProjection { fov, ..perspective.clone() });
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use bevy::utils::thiserror;
+use thiserror::Error;
+
+use std::path::PathBuf;
+
+#[cfg(not(target_arch = "wasm32"))]
+const SETTINGS_DIR: &str = "settings";
+
+/// Possible errors that can be produced when loading or saving settings
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    /// An [IO](std::io) Error
+    #[error("Could not access settings: {0}")]
+    Io(#[from] std::io::Error),
+    /// A [RON](ron) Error while parsing
+    #[error("Could not parse RON: {0}")]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    /// A [RON](ron) Error while writing
+    #[error("Could not write RON: {0}")]
+    RonError(#[from] ron::Error),
+    /// Browser storage unavailable or rejecting writes
+    #[cfg(target_arch = "wasm32")]
+    #[error("Could not access browser storage: {0}")]
+    Storage(String),
+}
+
+// per-user application data directory, falling back to the working directory
+pub fn user_data_dir() -> PathBuf {
+    let env_dir = |name: &str| std::env::var_os(name).filter(|dir| !dir.is_empty()).map(PathBuf::from);
+    let base = if cfg!(target_os = "windows") {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        env_dir("XDG_DATA_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".local/share")))
+    };
+    base.map_or_else(PathBuf::new, |base| base.join(env!("CARGO_PKG_NAME")))
+}
+
+// path of named settings file
+#[cfg(not(target_arch = "wasm32"))]
+fn settings_path(name: &str) -> PathBuf {
+    user_data_dir().join(SETTINGS_DIR).join(format!("{}.ron", name))
+}
+
+// saved ron string of named settings, none if never saved
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_settings(name: &str) -> Result<Option<String>, SettingsError> {
+    match std::fs::read_to_string(settings_path(name)) {
+        Ok(ron_str) => Ok(Some(ron_str)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_settings(name: &str, ron_str: &str) -> Result<(), SettingsError> {
+    std::fs::create_dir_all(user_data_dir().join(SETTINGS_DIR))?;
+    std::fs::write(settings_path(name), ron_str)?;
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage, SettingsError> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| SettingsError::Storage("localStorage unavailable".into()))
+}
+
+// saved ron string of named settings, none if never saved
+#[cfg(target_arch = "wasm32")]
+pub fn read_settings(name: &str) -> Result<Option<String>, SettingsError> {
+    local_storage()?.get_item(name)
+        .map_err(|err| SettingsError::Storage(format!("{:?}", err)))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write_settings(name: &str, ron_str: &str) -> Result<(), SettingsError> {
+    local_storage()?.set_item(name, ron_str)
+        .map_err(|err| SettingsError::Storage(format!("{:?}", err)))
+}
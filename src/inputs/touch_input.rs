@@ -0,0 +1,372 @@
+use bevy::prelude::*;
+use bevy::input::InputSystem;
+use bevy::input::touch::{TouchInput,TouchPhase};
+use bevy::utils::{HashMap,HashSet};
+use bevy::window::PrimaryWindow;
+use crate::character::MoverState;
+use crate::game_state::GameState;
+use crate::inputs::InputAction;
+use crate::loading::FontAssets;
+
+// joystick deflection in pixels reaching full movement
+const JOYSTICK_RADIUS: f32 = 60.;
+
+// on-screen control a touch can start on
+#[derive(Clone, Copy, Component, Debug, PartialEq)]
+pub enum TouchControl {
+    // held like the key of action
+    Button(InputAction),
+    // dragged up or down from its center to throttle, springs back when released
+    Throttle,
+}
+
+// what a touch controls, decided where it started
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TouchRole {
+    Joystick { origin: Vec2 },
+    Look { last: Vec2 },
+    Control(TouchControl, Rect),
+}
+
+// maintains per-frame state of the on-screen touch layer
+#[derive(Debug, Default, Resource)]
+pub struct TouchInputState {
+    // set once a touch was seen, shows the on-screen controls
+    pub enabled: bool,
+    // joystick deflection, x right and y forward, within unit circle
+    pub movement: Vec2,
+    // look drag since last frame, in pixels
+    pub look_delta: Vec2,
+    // -1 to 1, down to up
+    pub throttle: f32,
+    touches: HashMap<u64, TouchRole>,
+    // buttons touched this frame, pressed even if released again within it
+    tapped: HashSet<InputAction>,
+}
+
+// joystick deflection of a touch dragged by offset pixels, screen y points down
+pub fn joystick_deflection(offset: Vec2) -> Vec2 {
+    (Vec2::new(offset.x, -offset.y) / JOYSTICK_RADIUS).clamp_length_max(1.)
+}
+
+impl TouchInputState {
+    // action held by an on-screen button
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.tapped.contains(&action) || self.touches.values()
+            .any(|role| matches!(role, TouchRole::Control(TouchControl::Button(held), _) if *held == action))
+    }
+
+    // clears per-frame state before new touches are applied
+    pub fn begin_frame(&mut self) {
+        self.look_delta = Vec2::ZERO;
+        self.tapped.clear();
+    }
+
+    // applies touch, started on control with its screen rect if any, else on the left
+    // half of the screen moving or the right half looking
+    pub fn touch(&mut self, touch: &TouchInput, control: Option<(TouchControl, Rect)>, screen_width: f32) {
+        match touch.phase {
+            TouchPhase::Started => {
+                self.enabled = true;
+                let role = match control {
+                    Some((control, rect)) => TouchRole::Control(control, rect),
+                    None if touch.position.x < 0.5 * screen_width => TouchRole::Joystick { origin: touch.position },
+                    None => TouchRole::Look { last: touch.position },
+                };
+                if let TouchRole::Control(TouchControl::Button(action), _) = role {
+                    self.tapped.insert(action);
+                }
+                self.touches.insert(touch.id, role);
+                self.drag(role, touch.position);
+            }
+            TouchPhase::Moved => {
+                if let Some(role) = self.touches.get(&touch.id).copied() {
+                    self.drag(role, touch.position);
+                    if let TouchRole::Look { .. } = role {
+                        self.touches.insert(touch.id, TouchRole::Look { last: touch.position });
+                    }
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Canceled => {
+                match self.touches.remove(&touch.id) {
+                    Some(TouchRole::Joystick { .. }) => self.movement = Vec2::ZERO,
+                    Some(TouchRole::Control(TouchControl::Throttle, _)) => self.throttle = 0.,
+                    _ => {},
+                }
+            }
+        }
+    }
+
+    fn drag(&mut self, role: TouchRole, position: Vec2) {
+        match role {
+            TouchRole::Joystick { origin } => self.movement = joystick_deflection(position - origin),
+            TouchRole::Look { last } => self.look_delta += position - last,
+            TouchRole::Control(TouchControl::Throttle, rect) => {
+                self.throttle = ((rect.center().y - position.y) / (0.5 * rect.height()).max(1.)).clamp(-1., 1.);
+            }
+            TouchRole::Control(TouchControl::Button(_), _) => {},
+        }
+    }
+}
+
+// system state
+#[derive(Default, Resource)]
+pub struct TouchControlsState {
+    pub ui_entity: Option<Entity>,
+    pub throttle_entity: Option<Entity>,
+}
+
+/// On-screen touch controls, feeding the same actions as keys and mouse
+pub struct TouchInputPlugin;
+
+impl Plugin for TouchInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchInputState>();
+        app.init_resource::<TouchControlsState>();
+        app.add_systems(PreUpdate, update_touch_input.after(InputSystem));
+        app.add_systems(OnEnter(GameState::Running), setup_touch_controls);
+        app.add_systems(Update, update_touch_controls.run_if(in_state(GameState::Running)));
+        app.add_systems(OnExit(GameState::Running), exit_touch_controls);
+    }
+}
+
+fn update_touch_input(
+    mut touch_events: EventReader<TouchInput>,
+    mut state: ResMut<TouchInputState>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    controls_query: Query<(&Node, &GlobalTransform, &InheritedVisibility, &TouchControl)>,
+) {
+    state.begin_frame();
+    let screen_width = windows.get_single().map_or(0., |window| window.width());
+    for touch in touch_events.read() {
+        // topmost visible control under touch
+        let control = if touch.phase == TouchPhase::Started {
+            controls_query.iter()
+                .filter(|(_, _, visibility, _)| visibility.get())
+                .map(|(node, gt, _, control)| (*control, node.logical_rect(gt), gt.translation().z))
+                .filter(|(_, rect, _)| rect.contains(touch.position))
+                .max_by(|(_, _, z0), (_, _, z1)| z0.total_cmp(z1))
+                .map(|(control, rect, _)| (control, rect))
+        } else {
+            None
+        };
+        state.touch(touch, control, screen_width);
+    }
+}
+
+fn setup_touch_controls(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    mut controls_state: ResMut<TouchControlsState>,
+) {
+    let text_style = TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size: 18.0,
+        color: Color::rgba(0.9, 0.9, 0.9, 0.8),
+    };
+    let button = |control: TouchControl, width: f32, height: f32| (NodeBundle {
+        style: Style {
+            width: Val::Px(width),
+            height: Val::Px(height),
+            margin: UiRect::all(Val::Px(6.)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        background_color: Color::rgba(0.3, 0.5, 0.9, 0.25).into(),
+        ..default()
+    }, control);
+
+    let mut throttle_entity = None;
+    controls_state.ui_entity = Some(commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .with_children(|parent| {
+            // pause, top right
+            parent.spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn(button(TouchControl::Button(InputAction::Escape), 60., 40.))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section("II", text_style.clone()));
+                    });
+            });
+
+            // action buttons and throttle, bottom right above the toolbar
+            parent.spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.),
+                    bottom: Val::Px(110.),
+                    align_items: AlignItems::FlexEnd,
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (action, label) in [
+                        (InputAction::ToolUse, "Tool"),
+                        (InputAction::RotateY, "Rotate"),
+                        (InputAction::BuildToggle, "Build"),
+                        (InputAction::Use, "Use"),
+                        (InputAction::Jump, "Jump"),
+                    ] {
+                        parent.spawn(button(TouchControl::Button(action), 80., 56.))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(label, text_style.clone()));
+                            });
+                    }
+                });
+                throttle_entity = Some(parent.spawn(button(TouchControl::Throttle, 56., 260.))
+                    .insert(Visibility::Hidden)
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section("THR", text_style.clone()));
+                    }).id());
+            });
+        })
+        .id());
+    controls_state.throttle_entity = throttle_entity;
+}
+
+// shows controls once touched, and throttle while seated
+fn update_touch_controls(
+    touch_state: Res<TouchInputState>,
+    controls_state: Res<TouchControlsState>,
+    mover_query: Query<&MoverState>,
+    mut visibility_query: Query<&mut Visibility>,
+) {
+    let seated = mover_query.iter().any(|mover_state| mover_state.seated_in.is_some());
+    for (entity, visible) in [
+        (controls_state.ui_entity, touch_state.enabled),
+        (controls_state.throttle_entity, seated),
+    ] {
+        let visibility = if visible { Visibility::Inherited } else { Visibility::Hidden };
+        if let Some(mut entity_visibility) = entity.and_then(|entity| visibility_query.get_mut(entity).ok()) {
+            if *entity_visibility != visibility {
+                *entity_visibility = visibility;
+            }
+        }
+    }
+}
+
+fn exit_touch_controls(
+    mut commands: Commands,
+    mut controls_state: ResMut<TouchControlsState>,
+) {
+    if let Some(ui_entity) = controls_state.ui_entity.take() {
+        commands.entity(ui_entity).despawn_recursive();
+    }
+    controls_state.throttle_entity = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inputs::{GamepadInputMap,KeyInputMap,input_to_actions};
+    use bevy::transform::TransformSystem;
+    use bevy::ui::{UiScale,UiSurface,ui_layout_system};
+    use bevy::window::{WindowResized,WindowResolution,WindowScaleFactorChanged};
+
+    fn send_touch(app: &mut App, id: u64, phase: TouchPhase, position: Vec2) {
+        app.world.send_event(TouchInput { phase, position, force: None, id });
+    }
+
+    fn touch_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, TransformPlugin))
+            .add_event::<TouchInput>()
+            .add_event::<WindowResized>()
+            .add_event::<WindowScaleFactorChanged>()
+            .init_resource::<UiScale>()
+            .init_resource::<UiSurface>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Gamepads>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<KeyInputMap>()
+            .init_resource::<GamepadInputMap>()
+            .init_resource::<TouchInputState>()
+            .init_resource::<Input<InputAction>>()
+            .add_systems(PreUpdate, (update_touch_input, input_to_actions).chain())
+            .add_systems(PostUpdate, ui_layout_system.before(TransformSystem::TransformPropagate));
+        app.world.spawn((Window { resolution: WindowResolution::new(800., 600.), ..default() }, PrimaryWindow));
+        // tool button in the bottom right corner, laid out on first update
+        app.world.spawn((NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(700.),
+                top: Val::Px(500.),
+                width: Val::Px(80.),
+                height: Val::Px(56.),
+                ..default()
+            },
+            ..default()
+        }, TouchControl::Button(InputAction::ToolUse)))
+            .insert(InheritedVisibility::VISIBLE);
+        app.update();
+        app
+    }
+
+    #[test]
+    fn tool_button_uses_tool() {
+        let mut app = touch_app();
+        send_touch(&mut app, 1, TouchPhase::Started, Vec2::new(740., 528.));
+        app.update();
+        assert!(app.world.resource::<Input<InputAction>>().just_pressed(InputAction::ToolUse));
+
+        app.update();
+        let action_input = app.world.resource::<Input<InputAction>>();
+        assert!(action_input.pressed(InputAction::ToolUse) && !action_input.just_pressed(InputAction::ToolUse));
+
+        send_touch(&mut app, 1, TouchPhase::Ended, Vec2::new(740., 528.));
+        app.update();
+        assert!(!app.world.resource::<Input<InputAction>>().pressed(InputAction::ToolUse));
+
+        // tapped within one frame still uses the tool once
+        send_touch(&mut app, 2, TouchPhase::Started, Vec2::new(710., 510.));
+        send_touch(&mut app, 2, TouchPhase::Ended, Vec2::new(710., 510.));
+        app.update();
+        assert!(app.world.resource::<Input<InputAction>>().just_pressed(InputAction::ToolUse));
+    }
+
+    #[test]
+    fn touches_off_controls_move_and_look() {
+        let mut app = touch_app();
+        send_touch(&mut app, 1, TouchPhase::Started, Vec2::new(100., 300.));
+        send_touch(&mut app, 2, TouchPhase::Started, Vec2::new(500., 300.));
+        app.update();
+        send_touch(&mut app, 1, TouchPhase::Moved, Vec2::new(100., 300. - JOYSTICK_RADIUS));
+        send_touch(&mut app, 2, TouchPhase::Moved, Vec2::new(520., 290.));
+        app.update();
+        let state = app.world.resource::<TouchInputState>();
+        assert_eq!(state.movement, Vec2::Y);
+        assert_eq!(state.look_delta, Vec2::new(20., -10.));
+        assert!(!app.world.resource::<Input<InputAction>>().pressed(InputAction::ToolUse));
+
+        send_touch(&mut app, 1, TouchPhase::Ended, Vec2::new(100., 240.));
+        app.update();
+        assert_eq!(app.world.resource::<TouchInputState>().movement, Vec2::ZERO);
+    }
+}
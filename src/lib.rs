@@ -18,8 +18,8 @@ use crate::loading::{LoadingUiStatePlugin,PreLoadingPlugin,
     AssetLoadingPlugin,SceneLoadingPlugin};
 use crate::menu::MenuPlugin;
 use crate::character::CharacterFpsPlugin;
-use crate::inputs::{KeyInputPlugin, MouseInputPlugin};
-use crate::building::BuildingStatePlugin;
+use crate::inputs::{GamepadInputPlugin, KeyInputPlugin, MouseInputPlugin, TouchInputPlugin};
+use crate::building::{BuildingStatePlugin,PartsAssetLoaderPlugin};
 use crate::props::PropsStatesPlugin;
 use crate::world::{WorldAssetLoaderPlugin,WorldLoadingPlugin,WorldStatePlugin,
     WorldTerrainPlugin};
@@ -51,9 +51,12 @@ impl Plugin for GamePlugin {
             AssetLoadingPlugin,
             SceneLoadingPlugin,
             WorldAssetLoaderPlugin,
+            PartsAssetLoaderPlugin,
             WorldLoadingPlugin,
             KeyInputPlugin,
             MouseInputPlugin,
+            GamepadInputPlugin,
+            TouchInputPlugin,
         ))
         .add_plugins((
             WorldStatePlugin,
@@ -1,8 +1,15 @@
+use crate::building::PartsAsset;
 use crate::game_state::GameState;
+use crate::loading::{LoadingUiEvent,LoadingUiEventAction};
+use crate::world::WorldAsset;
+use bevy::asset::LoadState;
 use bevy::prelude::*;
+use bevy::utils::HashMap;
 use bevy_asset_loader::prelude::*;
 use bevy_kira_audio::AudioSource;
 
+pub const BUILDING_KIT_PARTS_PATH: &str = "props/building_kit.parts.ron";
+
 pub struct AssetLoadingPlugin;
 
 impl Plugin for AssetLoadingPlugin {
@@ -14,7 +21,10 @@ impl Plugin for AssetLoadingPlugin {
         .add_collection_to_loading_state::<_, AudioAssets>(GameState::AssetLoading)
         .add_collection_to_loading_state::<_, IconAssets>(GameState::AssetLoading)
         .add_collection_to_loading_state::<_, TextureAssets>(GameState::AssetLoading)
+        .add_collection_to_loading_state::<_, PartsAssets>(GameState::AssetLoading)
+        .add_collection_to_loading_state::<_, WorldAssets>(GameState::AssetLoading)
         ;
+        app.add_systems(Update, report_parts_load_failure.run_if(in_state(GameState::AssetLoading)));
     }
 }
 
@@ -39,16 +49,37 @@ pub struct IconAssets {
     pub hammer: Handle<Image>,
     #[asset(path = "textures/icon_unbuild.png")]
     pub unbuild: Handle<Image>,
-    #[asset(path = "textures/icon_metal_frame.png")]
-    pub metal_frame: Handle<Image>,
-    #[asset(path = "textures/icon_fuel_tank.png")]
-    pub fuel_tank: Handle<Image>,
-    #[asset(path = "textures/icon_nose_cone.png")]
-    pub nose_cone: Handle<Image>,
-    #[asset(path = "textures/icon_thruster.png")]
-    pub thruster: Handle<Image>,
-    #[asset(path = "textures/icon_flight_seat.png")]
-    pub flight_seat: Handle<Image>,
+}
+
+#[derive(Resource)]
+pub struct PartsAssets {
+    pub building_kit: Handle<PartsAsset>,
+}
+
+// implemented by hand to share the path with the load failure report
+impl AssetCollection for PartsAssets {
+    fn create(world: &mut World) -> Self {
+        Self {
+            building_kit: world.resource::<AssetServer>().load(BUILDING_KIT_PARTS_PATH),
+        }
+    }
+
+    fn load(world: &mut World) -> Vec<UntypedHandle> {
+        vec![world.resource::<AssetServer>().load::<PartsAsset>(BUILDING_KIT_PARTS_PATH).untyped()]
+    }
+}
+
+#[derive(AssetCollection,Resource)]
+pub struct WorldAssets {
+    #[asset(paths("worlds/world01.world"), collection(typed, mapped))]
+    pub worlds: HashMap<String, Handle<WorldAsset>>,
+}
+
+impl WorldAssets {
+    // world definition of world name
+    pub fn world(&self, name: &str) -> Option<&Handle<WorldAsset>> {
+        self.worlds.get(&format!("worlds/{}.world", name))
+    }
 }
 
 #[derive(AssetCollection,Resource)]
@@ -62,3 +93,24 @@ pub struct TextureAssets {
     #[asset(path = "textures/explosion.png")]
     pub explosion_static: Handle<Image>,
 }
+
+// parse errors are logged by the asset server, loading would otherwise stall silently
+fn report_parts_load_failure(
+    asset_server: Res<AssetServer>,
+    mut loading_ui_events: EventWriter<LoadingUiEvent>,
+    mut reported: Local<bool>,
+) {
+    if *reported {
+        return;
+    }
+    if let Some(handle) = asset_server.get_handle::<PartsAsset>(BUILDING_KIT_PARTS_PATH) {
+        if asset_server.load_state(&handle) == LoadState::Failed {
+            error!("Failed to load part definitions, see log for details");
+            loading_ui_events.send(LoadingUiEvent {
+                action: LoadingUiEventAction::SetText,
+                payload: Some("Failed to load part definitions".into()),
+            });
+            *reported = true;
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use crate::game_state::GameState;
+use crate::inputs::{InputAction,KeyBinding,KeyInputMap,KeyModifier};
+use crate::loading::FontAssets;
+use crate::menu::ButtonColors;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+
+// system state
+#[derive(Default, Resource)]
+pub struct ControlsMenuState {
+    pub ui_entity: Option<Entity>,
+    // action waiting for a key press to be rebound
+    pub listening: Option<InputAction>,
+    pub status: String,
+}
+
+// buttons of the controls screen, and the one opening it from other menus
+#[derive(Clone, Copy, Component, PartialEq)]
+pub enum ControlsButton {
+    Open,
+    Reset,
+    Back,
+}
+
+#[derive(Component)]
+struct BindingButton(InputAction);
+
+// Tag for UI component
+#[derive(Component)]
+struct BindingText(InputAction);
+
+// Tag for UI component
+#[derive(Component)]
+struct ControlsStatusText;
+
+/// Controls settings screen, rebinding keys from the main and pause menus
+pub struct ControlsMenuPlugin;
+
+impl Plugin for ControlsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(ControlsMenuState::default());
+
+        app.add_systems(Update, (
+            click_controls_buttons,
+            update_controls_listening,
+            update_controls_texts,
+        ).chain().run_if(in_state(GameState::Menu).or_else(in_state(GameState::Paused))));
+        app.add_systems(OnExit(GameState::Menu), exit_controls_menu);
+        app.add_systems(OnExit(GameState::Paused), exit_controls_menu);
+    }
+}
+
+// spawns a menu button with a text label
+pub fn spawn_controls_open_button(parent: &mut ChildBuilder, font_assets: &FontAssets) {
+    parent.spawn((ButtonBundle {
+        style: Style {
+            width: Val::Px(160.0),
+            height: Val::Px(50.0),
+            margin: UiRect::all(Val::Px(10.)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        background_color: ButtonColors::default().normal.into(),
+        ..Default::default()
+    },
+    ButtonColors::default(),
+    ControlsButton::Open))
+    .with_children(|parent| {
+        parent.spawn(TextBundle::from_section("Controls", TextStyle {
+            font: font_assets.fira_sans.clone(),
+            font_size: 30.0,
+            color: Color::rgb(0.9, 0.9, 0.9),
+        }));
+    });
+}
+
+fn spawn_controls_menu(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+) -> Entity {
+    let text_style = |font_size: f32| TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    let button = |width: f32, height: f32| ButtonBundle {
+        style: Style {
+            width: Val::Px(width),
+            height: Val::Px(height),
+            margin: UiRect::all(Val::Px(2.)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        background_color: ButtonColors::default().normal.into(),
+        ..Default::default()
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            background_color: Color::rgba(0.05, 0.05, 0.05, 0.95).into(),
+            focus_policy: FocusPolicy::Block,
+            z_index: ZIndex::Global(10),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Controls", text_style(40.)));
+            parent.spawn(TextBundle::from_section(" ", text_style(18.))
+                .with_style(Style { margin: UiRect::all(Val::Px(8.)), ..default() }))
+                .insert(ControlsStatusText);
+
+            // binding rows, wrapped into columns
+            parent.spawn(NodeBundle {
+                style: Style {
+                    width: Val::Percent(90.0),
+                    flex_direction: FlexDirection::Row,
+                    flex_wrap: FlexWrap::Wrap,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|parent| {
+                for action in InputAction::ALL.iter() {
+                    parent.spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(330.),
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(action.label(), text_style(16.))
+                            .with_style(Style { width: Val::Px(220.), ..default() }));
+                        parent.spawn((button(100., 22.), ButtonColors::default(), BindingButton(*action)))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(" ", text_style(16.)))
+                                    .insert(BindingText(*action));
+                            });
+                    });
+                }
+            });
+
+            parent.spawn(NodeBundle {
+                style: Style {
+                    margin: UiRect::all(Val::Px(12.)),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn((button(180., 40.), ButtonColors::default(), ControlsButton::Reset))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section("Reset defaults", text_style(24.)));
+                    });
+                parent.spawn((button(180., 40.), ButtonColors::default(), ControlsButton::Back))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section("Back", text_style(24.)));
+                    });
+            });
+        })
+        .id()
+}
+
+fn save_bindings(input_map: &KeyInputMap) {
+    if let Err(err) = input_map.save() {
+        error!("Failed to save key bindings: {}", err);
+    }
+}
+
+fn click_controls_buttons(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    mut controls_state: ResMut<ControlsMenuState>,
+    mut input_map: ResMut<KeyInputMap>,
+    controls_buttons: Query<(&Interaction, &ControlsButton), (Changed<Interaction>, With<Button>)>,
+    binding_buttons: Query<(&Interaction, &BindingButton), (Changed<Interaction>, With<Button>)>,
+) {
+    for (interaction, controls_button) in controls_buttons.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match controls_button {
+            ControlsButton::Open => {
+                if controls_state.ui_entity.is_none() {
+                    controls_state.ui_entity = Some(spawn_controls_menu(&mut commands, &font_assets));
+                    controls_state.listening = None;
+                    controls_state.status = "Click a binding, then press a key".into();
+                }
+            }
+            ControlsButton::Reset => {
+                *input_map = KeyInputMap::default();
+                save_bindings(&input_map);
+                controls_state.listening = None;
+                controls_state.status = "Restored default bindings".into();
+            }
+            ControlsButton::Back => {
+                if let Some(ui_entity) = controls_state.ui_entity.take() {
+                    commands.entity(ui_entity).despawn_recursive();
+                }
+                controls_state.listening = None;
+            }
+        }
+    }
+
+    for (interaction, binding_button) in binding_buttons.iter() {
+        if *interaction == Interaction::Pressed {
+            controls_state.listening = Some(binding_button.0);
+            controls_state.status = format!("Press a key for {} (Esc cancels)", binding_button.0.label());
+        }
+    }
+}
+
+// binds next pressed key to the listening action, with any modifier held as a chord.
+// a modifier key binds by itself when released without another key
+fn update_controls_listening(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut controls_state: ResMut<ControlsMenuState>,
+    mut input_map: ResMut<KeyInputMap>,
+) {
+    let Some(action) = controls_state.listening else {
+        return;
+    };
+    let held_modifier = KeyModifier::ALL.iter().copied()
+        .find(|modifier| keyboard_input.any_pressed(modifier.keys()));
+    let binding = if let Some(key) = keyboard_input.get_just_pressed()
+        .find(|key| KeyModifier::of_key(**key).is_none()) {
+        KeyBinding { key: *key, modifier: held_modifier }
+    } else if let Some(key) = keyboard_input.get_just_released()
+        .find(|key| KeyModifier::of_key(**key).is_some()) {
+        KeyBinding::key(*key)
+    } else {
+        return;
+    };
+    controls_state.listening = None;
+    if binding == KeyBinding::key(KeyCode::Escape) && action != InputAction::Escape {
+        controls_state.status = "Cancelled".into();
+        return;
+    }
+
+    controls_state.status = match input_map.bind(action, binding) {
+        Some(other) => format!("{} was bound to {}, swapped to {}",
+            binding.label(), other.label(), input_map.binding(other).label()),
+        None => format!("{} bound to {}", action.label(), binding.label()),
+    };
+    save_bindings(&input_map);
+}
+
+fn update_controls_texts(
+    controls_state: Res<ControlsMenuState>,
+    input_map: Res<KeyInputMap>,
+    mut binding_texts: Query<(&BindingText, &mut Text), Without<ControlsStatusText>>,
+    mut status_texts: Query<&mut Text, With<ControlsStatusText>>,
+    added: Query<(), Added<BindingText>>,
+) {
+    if !controls_state.is_changed() && !input_map.is_changed() && added.is_empty() {
+        return;
+    }
+    for (binding_text, mut text) in binding_texts.iter_mut() {
+        text.sections[0].value = if controls_state.listening == Some(binding_text.0) {
+            "...".into()
+        } else {
+            input_map.binding(binding_text.0).label()
+        };
+    }
+    for mut text in status_texts.iter_mut() {
+        text.sections[0].value = controls_state.status.clone();
+    }
+}
+
+fn exit_controls_menu(
+    mut commands: Commands,
+    mut controls_state: ResMut<ControlsMenuState>,
+) {
+    if let Some(ui_entity) = controls_state.ui_entity.take() {
+        commands.entity(ui_entity).despawn_recursive();
+    }
+    controls_state.listening = None;
+}
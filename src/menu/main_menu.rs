@@ -1,7 +1,8 @@
 use crate::inputs::CursorLockState;
 use crate::loading::{FontAssets,LoadingUiState,LoadingUiEvent,LoadingUiEventAction};
 use crate::game_state::GameState;
-use crate::menu::{CreditsStatePlugin,PauseMenuStatePlugin};
+use crate::menu::{ControlsMenuPlugin,CreditsStatePlugin,PauseMenuStatePlugin,SettingsMenuPlugin,
+    spawn_controls_open_button};
 use crate::world::WorldState;
 use bevy::prelude::*;
 use bevy::window::CursorGrabMode;
@@ -32,7 +33,9 @@ impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_plugins((CreditsStatePlugin,
-                PauseMenuStatePlugin))
+                PauseMenuStatePlugin,
+                ControlsMenuPlugin,
+                SettingsMenuPlugin))
             .insert_resource(MainMenuState::default());
         
         app.add_systems(OnEnter(GameState::Menu), setup_menu);
@@ -144,6 +147,8 @@ fn setup_menu(
                         ..Default::default()
                     });
                 });
+
+            spawn_controls_open_button(parent, &font_assets);
         })
         .id());
 
@@ -1,6 +1,10 @@
+mod controls_menu;
+pub use controls_menu::*;
 mod credits;
 pub use credits::*;
 mod main_menu;
 pub use main_menu::*;
 mod pause_menu;
 pub use pause_menu::*;
+mod settings_menu;
+pub use settings_menu::*;
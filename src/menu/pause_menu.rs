@@ -1,7 +1,7 @@
 use crate::game_state::GameState;
 use crate::inputs::CursorLockState;
 use crate::loading::FontAssets;
-use crate::menu::ButtonColors;
+use crate::menu::{ButtonColors,spawn_controls_open_button,spawn_settings_open_button};
 use bevy::prelude::*;
 use bevy::window::CursorGrabMode;
 use bevy_rapier3d::prelude::*;
@@ -42,36 +42,55 @@ fn enter_pause_menu(
     // pause menu ui
     let button_colors = ButtonColors::default();
     pause_menu_state.ui_entity = Some(commands
-        .spawn((ButtonBundle {
+        .spawn(NodeBundle {
             style: Style {
-                width: Val::Px(160.0), 
-                height: Val::Px(50.0),
-                margin: UiRect::all(Val::Auto),
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
                 justify_content: JustifyContent::Center,
                 align_items: AlignItems::Center,
-                ..Default::default()
+                flex_direction: FlexDirection::Column,
+                ..default()
             },
-            background_color: button_colors.normal.into(),
-            ..Default::default()
-        },
-        ButtonColors::default(),
-        ChangeState(GameState::Paused)))
+            background_color: Color::NONE.into(),
+            ..default()
+        })
         .with_children(|parent| {
-            parent.spawn(TextBundle {
-                text: Text {
-                    sections: vec![TextSection {
-                        value: "Resume".to_string(),
-                        style: TextStyle {
-                            font: font_assets.fira_sans.clone(),
-                            font_size: 40.0,
-                            color: Color::rgb(0.9, 0.9, 0.9),
-                        },
-                    }],
-                    linebreak_behavior: bevy::text::BreakLineOn::WordBoundary,
-                    alignment: Default::default(),
+            parent
+                .spawn((ButtonBundle {
+                    style: Style {
+                        width: Val::Px(160.0), 
+                        height: Val::Px(50.0),
+                        margin: UiRect::all(Val::Px(10.)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    background_color: button_colors.normal.into(),
+                    ..Default::default()
                 },
-                ..Default::default()
-            });
+                ButtonColors::default(),
+                ChangeState(GameState::Paused)))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle {
+                        text: Text {
+                            sections: vec![TextSection {
+                                value: "Resume".to_string(),
+                                style: TextStyle {
+                                    font: font_assets.fira_sans.clone(),
+                                    font_size: 40.0,
+                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                },
+                            }],
+                            linebreak_behavior: bevy::text::BreakLineOn::WordBoundary,
+                            alignment: Default::default(),
+                        },
+                        ..Default::default()
+                    });
+                });
+
+            spawn_controls_open_button(parent, &font_assets);
+            spawn_settings_open_button(parent, &font_assets);
         }).id());
 
     // pause physics
@@ -100,15 +119,17 @@ fn click_play_button(
     mut cursor_lock_controls: ResMut<CursorLockState>,
     mut windows: Query<&mut Window>,
 ) {
-    for (interaction, mut color, button_colors, _change_state) in &mut interaction_query {
+    for (interaction, mut color, button_colors, change_state) in &mut interaction_query {
         match *interaction {
             Interaction::Pressed => {
-                next_state.set(GameState::Running);
-                // request cursor lock
-                let mut window = windows.single_mut();
-                window.cursor.grab_mode = CursorGrabMode::Locked;
-                window.cursor.visible = false;
-                cursor_lock_controls.enabled = true;
+                if change_state.is_some() {
+                    next_state.set(GameState::Running);
+                    // request cursor lock
+                    let mut window = windows.single_mut();
+                    window.cursor.grab_mode = CursorGrabMode::Locked;
+                    window.cursor.visible = false;
+                    cursor_lock_controls.enabled = true;
+                }
             }
             Interaction::Hovered => {
                 *color = button_colors.hovered.into();
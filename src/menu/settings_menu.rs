@@ -0,0 +1,307 @@
+use crate::game_state::GameState;
+use crate::inputs::{GamepadInputSettings,MouseSettings};
+use crate::loading::FontAssets;
+use crate::menu::ButtonColors;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+
+// change of settings per button press
+const SENSITIVITY_STEP: f32 = 0.0002;
+const FOV_STEP: f32 = 5.;
+const SMOOTHING_STEP: f32 = 0.02;
+const DEADZONE_STEP: f32 = 0.05;
+const LOOK_SENSITIVITY_STEP: f32 = 0.5;
+
+// system state
+#[derive(Default, Resource)]
+pub struct SettingsMenuState {
+    pub ui_entity: Option<Entity>,
+}
+
+// mouse or gamepad setting edited by a row of the settings screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputSetting {
+    Sensitivity,
+    InvertY,
+    Fov,
+    Smoothing,
+    GamepadDeadzone,
+    GamepadLookSensitivity,
+}
+
+impl InputSetting {
+    const ALL: [InputSetting; 6] = [
+        InputSetting::Sensitivity,
+        InputSetting::InvertY,
+        InputSetting::Fov,
+        InputSetting::Smoothing,
+        InputSetting::GamepadDeadzone,
+        InputSetting::GamepadLookSensitivity,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            InputSetting::Sensitivity => "Mouse sensitivity",
+            InputSetting::InvertY => "Invert Y",
+            InputSetting::Fov => "Field of view",
+            InputSetting::Smoothing => "Look smoothing",
+            InputSetting::GamepadDeadzone => "Stick deadzone",
+            InputSetting::GamepadLookSensitivity => "Stick look sensitivity",
+        }
+    }
+
+    pub fn value_label(self, mouse: &MouseSettings, gamepad: &GamepadInputSettings) -> String {
+        match self {
+            InputSetting::Sensitivity => format!("{:.1}", mouse.sensitivity * 1000.),
+            InputSetting::InvertY => if mouse.invert_y { "On".into() } else { "Off".into() },
+            InputSetting::Fov => format!("{:.0}°", mouse.fov),
+            InputSetting::Smoothing => format!("{:.2} s", mouse.smoothing),
+            InputSetting::GamepadDeadzone => format!("{:.0}%", gamepad.deadzone * 100.),
+            InputSetting::GamepadLookSensitivity => format!("{:.1}", gamepad.look_sensitivity),
+        }
+    }
+
+    // settings changed by steps, toggled by any step
+    pub fn adjust(
+        self,
+        mouse: &MouseSettings,
+        gamepad: &GamepadInputSettings,
+        steps: i32,
+    ) -> (MouseSettings, GamepadInputSettings) {
+        let steps = steps as f32;
+        let (mut mouse, mut gamepad) = (mouse.clone(), gamepad.clone());
+        match self {
+            InputSetting::Sensitivity => mouse.sensitivity += steps * SENSITIVITY_STEP,
+            InputSetting::InvertY => mouse.invert_y = !mouse.invert_y,
+            InputSetting::Fov => mouse.fov += steps * FOV_STEP,
+            InputSetting::Smoothing => mouse.smoothing += steps * SMOOTHING_STEP,
+            InputSetting::GamepadDeadzone => gamepad.deadzone += steps * DEADZONE_STEP,
+            InputSetting::GamepadLookSensitivity => gamepad.look_sensitivity += steps * LOOK_SENSITIVITY_STEP,
+        }
+        (mouse.clamped(), gamepad.clamped())
+    }
+}
+
+// buttons of the settings screen, and the one opening it from the pause menu
+#[derive(Clone, Copy, Component, PartialEq)]
+pub enum SettingsButton {
+    Open,
+    Adjust(InputSetting, i32),
+    Reset,
+    Back,
+}
+
+// Tag for UI component
+#[derive(Component)]
+struct SettingValueText(InputSetting);
+
+/// Settings screen of the pause menu, editing saved mouse and gamepad settings
+pub struct SettingsMenuPlugin;
+
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(SettingsMenuState::default());
+
+        app.add_systems(Update, (
+            click_settings_buttons,
+            update_settings_texts,
+        ).chain().run_if(in_state(GameState::Paused)));
+        app.add_systems(OnExit(GameState::Paused), exit_settings_menu);
+    }
+}
+
+// spawns a menu button with a text label
+pub fn spawn_settings_open_button(parent: &mut ChildBuilder, font_assets: &FontAssets) {
+    parent.spawn((ButtonBundle {
+        style: Style {
+            width: Val::Px(160.0),
+            height: Val::Px(50.0),
+            margin: UiRect::all(Val::Px(10.)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        background_color: ButtonColors::default().normal.into(),
+        ..Default::default()
+    },
+    ButtonColors::default(),
+    SettingsButton::Open))
+    .with_children(|parent| {
+        parent.spawn(TextBundle::from_section("Settings", TextStyle {
+            font: font_assets.fira_sans.clone(),
+            font_size: 30.0,
+            color: Color::rgb(0.9, 0.9, 0.9),
+        }));
+    });
+}
+
+fn spawn_settings_menu(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+) -> Entity {
+    let text_style = |font_size: f32| TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    let button = |width: f32, height: f32| ButtonBundle {
+        style: Style {
+            width: Val::Px(width),
+            height: Val::Px(height),
+            margin: UiRect::all(Val::Px(2.)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        background_color: ButtonColors::default().normal.into(),
+        ..Default::default()
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            background_color: Color::rgba(0.05, 0.05, 0.05, 0.95).into(),
+            focus_policy: FocusPolicy::Block,
+            z_index: ZIndex::Global(10),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Settings", text_style(40.))
+                .with_style(Style { margin: UiRect::all(Val::Px(12.)), ..default() }));
+
+            for setting in InputSetting::ALL.iter() {
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        margin: UiRect::all(Val::Px(4.)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(setting.label(), text_style(20.))
+                        .with_style(Style { width: Val::Px(280.), ..default() }));
+                    if *setting != InputSetting::InvertY {
+                        parent.spawn((button(36., 30.), ButtonColors::default(), SettingsButton::Adjust(*setting, -1)))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section("-", text_style(24.)));
+                            });
+                    }
+                    parent.spawn((button(100., 30.), ButtonColors::default(), SettingsButton::Adjust(*setting, 0)))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(" ", text_style(20.)))
+                                .insert(SettingValueText(*setting));
+                        });
+                    if *setting != InputSetting::InvertY {
+                        parent.spawn((button(36., 30.), ButtonColors::default(), SettingsButton::Adjust(*setting, 1)))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section("+", text_style(24.)));
+                            });
+                    }
+                });
+            }
+
+            parent.spawn(NodeBundle {
+                style: Style {
+                    margin: UiRect::all(Val::Px(12.)),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn((button(180., 40.), ButtonColors::default(), SettingsButton::Reset))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section("Reset defaults", text_style(24.)));
+                    });
+                parent.spawn((button(180., 40.), ButtonColors::default(), SettingsButton::Back))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section("Back", text_style(24.)));
+                    });
+            });
+        })
+        .id()
+}
+
+fn click_settings_buttons(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    mut settings_menu_state: ResMut<SettingsMenuState>,
+    mut mouse_settings: ResMut<MouseSettings>,
+    mut gamepad_settings: ResMut<GamepadInputSettings>,
+    interaction_query: Query<(&Interaction, &SettingsButton), (Changed<Interaction>, With<Button>)>,
+) {
+    for (interaction, settings_button) in interaction_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let changed = match settings_button {
+            SettingsButton::Open => {
+                if settings_menu_state.ui_entity.is_none() {
+                    settings_menu_state.ui_entity = Some(spawn_settings_menu(&mut commands, &font_assets));
+                }
+                None
+            }
+            // value button toggles, or does nothing for stepped settings
+            SettingsButton::Adjust(setting, steps) => {
+                if *steps != 0 || *setting == InputSetting::InvertY {
+                    Some(setting.adjust(&mouse_settings, &gamepad_settings, *steps))
+                } else {
+                    None
+                }
+            }
+            SettingsButton::Reset => Some((MouseSettings::default(), GamepadInputSettings::default())),
+            SettingsButton::Back => {
+                if let Some(ui_entity) = settings_menu_state.ui_entity.take() {
+                    commands.entity(ui_entity).despawn_recursive();
+                }
+                None
+            }
+        };
+
+        let Some((mouse, gamepad)) = changed else {
+            continue;
+        };
+        if mouse != *mouse_settings {
+            *mouse_settings = mouse;
+            if let Err(err) = mouse_settings.save() {
+                error!("Failed to save mouse settings: {}", err);
+            }
+        }
+        if gamepad != *gamepad_settings {
+            *gamepad_settings = gamepad;
+            if let Err(err) = gamepad_settings.save() {
+                error!("Failed to save gamepad settings: {}", err);
+            }
+        }
+    }
+}
+
+fn update_settings_texts(
+    mouse_settings: Res<MouseSettings>,
+    gamepad_settings: Res<GamepadInputSettings>,
+    mut value_texts: Query<(Ref<SettingValueText>, &mut Text)>,
+) {
+    for (value_text, mut text) in value_texts.iter_mut() {
+        if mouse_settings.is_changed() || gamepad_settings.is_changed() || value_text.is_added() {
+            text.sections[0].value = value_text.0.value_label(&mouse_settings, &gamepad_settings);
+        }
+    }
+}
+
+fn exit_settings_menu(
+    mut commands: Commands,
+    mut settings_menu_state: ResMut<SettingsMenuState>,
+) {
+    if let Some(ui_entity) = settings_menu_state.ui_entity.take() {
+        commands.entity(ui_entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,144 @@
+use crate::building::{BpInfo,BpInfos,DiscreteVec3,Grid,GridBlock};
+use crate::game_state::GameState;
+use crate::world::Atmosphere;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+
+// area of a block face
+const FACE_AREA: f32 = 1.;
+// outward face normals of a block, in grid-local coordinates
+const FACE_NORMALS: [Vec3; 6] = [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z];
+
+// pressure force on a flat face moving through air with velocity,
+// zero unless the face points into the flow. angled faces produce lift
+pub fn face_drag(
+    air_density: f32,
+    drag_coefficient: f32,
+    area: f32,
+    normal: Vec3,
+    velocity: Vec3,
+) -> Vec3 {
+    let normal_speed = velocity.dot(normal);
+    if normal_speed <= 0. {
+        return Vec3::ZERO;
+    }
+    -0.5 * air_density * drag_coefficient * area * normal_speed * normal_speed * normal
+}
+
+// drag coefficient of block face in grid-local coordinates, none if covered by another block.
+// the streamlined nose of a part uses its nose drag
+pub fn exposed_face_drag(
+    grid: &Grid,
+    bp_info: &BpInfo,
+    translation: Vec3,
+    rotation: Quat,
+    face_normal: Vec3,
+) -> Option<f32> {
+    if grid.solid_blocks.contains_key(&DiscreteVec3::from(translation + face_normal)) {
+        return None;
+    }
+    let nose_normal = rotation.mul_vec3(Vec3::Y);
+    Some(match bp_info.nose_drag {
+        Some(nose_drag) if nose_normal.dot(face_normal) > 0.9 => nose_drag,
+        _ => bp_info.drag,
+    })
+}
+
+pub struct AeroStatePlugin;
+
+impl Plugin for AeroStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                update_aero_state.run_if(in_state(GameState::Running)),));
+    }
+}
+
+// applies drag of exposed block faces onto grids.
+// uses impulses since rapier clears them after every step,
+// leaving ExternalForce to thrusters
+fn update_aero_state(
+    time: Res<Time>,
+    infos: Res<BpInfos>,
+    atmosphere: Res<Atmosphere>,
+    blocks_query: Query<&GridBlock>,
+    mut grids_query: Query<(Entity, &Grid, &GlobalTransform, &Velocity,
+        Option<&ReadMassProperties>, &mut ExternalImpulse)>,
+) {
+    let dt = time.delta_seconds();
+    let mut grid_blocks: HashMap<Entity, Vec<&GridBlock>> = HashMap::new();
+    for gb in blocks_query.iter() {
+        grid_blocks.entry(gb.entity).or_default().push(gb);
+    }
+
+    for (grid_entity, grid, grid_gt, velocity, mass_props, mut impulse) in grids_query.iter_mut() {
+        let Some(blocks) = grid_blocks.get(&grid_entity) else {
+            continue;
+        };
+        let local_com = mass_props.map_or(Vec3::ZERO, |mp| mp.local_center_of_mass);
+        let center_of_mass = grid_gt.transform_point(local_com);
+        let (_, grid_rotation, _) = grid_gt.to_scale_rotation_translation();
+
+        let mut force = Vec3::ZERO;
+        let mut torque = Vec3::ZERO;
+        for gb in blocks.iter() {
+            let (Some((translation, rotation, _)), Some(bp_info)) = (gb.collider.as_ref(), infos.bps.get(&gb.bp_name)) else {
+                continue;
+            };
+            let block_center = grid_gt.transform_point(*translation);
+            for face_normal in FACE_NORMALS.iter() {
+                let Some(drag_coefficient) = exposed_face_drag(grid, bp_info, *translation, *rotation, *face_normal) else {
+                    continue;
+                };
+                let normal = grid_rotation.mul_vec3(*face_normal);
+                let face_center = block_center + 0.5 * normal;
+                let face_velocity = velocity.linvel + velocity.angvel.cross(face_center - center_of_mass);
+                let face_force = face_drag(atmosphere.density(face_center.y), drag_coefficient, FACE_AREA, normal, face_velocity);
+                force += face_force;
+                torque += (face_center - center_of_mass).cross(face_force);
+            }
+        }
+
+        // drag slows the grid but never reverses it within a step
+        let mass = mass_props.map_or(0., |mp| mp.mass);
+        let mut linear_impulse = force * dt;
+        if mass > 0. && linear_impulse.length() > mass * velocity.linvel.length() {
+            linear_impulse = linear_impulse.normalize_or_zero() * mass * velocity.linvel.length();
+        }
+        impulse.impulse += linear_impulse;
+        impulse.torque_impulse += torque * dt;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::building::GridSolidity;
+
+    #[test]
+    fn faces_drag_only_into_flow() {
+        let drag = face_drag(1., 1., FACE_AREA, Vec3::Y, Vec3::new(0., 2., 0.));
+        assert_eq!(drag, Vec3::new(0., -2., 0.));
+        assert_eq!(face_drag(1., 1., FACE_AREA, Vec3::NEG_Y, Vec3::new(0., 2., 0.)), Vec3::ZERO);
+    }
+
+    #[test]
+    fn nose_cone_shields_covered_faces() {
+        let cone = BpInfo { drag: 1., nose_drag: Some(0.2), ..default() };
+        let mut grid = Grid::default();
+        // cone on top of a block, nose pointing up
+        grid.solid_blocks.insert(DiscreteVec3::from(Vec3::ZERO), GridSolidity::Solid);
+        grid.solid_blocks.insert(DiscreteVec3::from(Vec3::Y), GridSolidity::Solid);
+        let drag_of = |face_normal| exposed_face_drag(&grid, &cone, Vec3::Y, Quat::IDENTITY, face_normal);
+        assert_eq!(drag_of(Vec3::Y), Some(0.2));
+        assert_eq!(drag_of(Vec3::NEG_Y), None);
+        assert_eq!(drag_of(Vec3::X), Some(1.));
+
+        // exposed block face drags more than the nose covering it
+        let flow = Vec3::new(0., 10., 0.);
+        let exposed = face_drag(1., cone.drag, FACE_AREA, Vec3::Y, flow);
+        let nose = face_drag(1., drag_of(Vec3::Y).unwrap(), FACE_AREA, Vec3::Y, flow);
+        assert!(nose.length() < exposed.length());
+    }
+}
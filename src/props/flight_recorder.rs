@@ -0,0 +1,309 @@
+use crate::actions::BuildingActionsState;
+use crate::building::{Grid,GridBlock};
+use crate::character::MoverState;
+use crate::game_state::GameState;
+use crate::inputs::{KeyInputState,user_data_dir};
+use crate::props::{FuelTank,piloted_grid,ThrustersState,throttle_percent};
+use crate::world::WorldOrigin;
+
+use bevy::prelude::*;
+use bevy::utils::thiserror;
+use thiserror::Error;
+use bevy_rapier3d::prelude::*;
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+const FLIGHTS_DIR: &str = "flights";
+// seconds between samples
+const SAMPLE_INTERVAL: f32 = 0.1;
+
+const CSV_HEADER: &str = "time,pos_x,pos_y,pos_z,rot_x,rot_y,rot_z,rot_w,\
+    linvel_x,linvel_y,linvel_z,angvel_x,angvel_y,angvel_z,mass,throttle,fuel";
+
+/// Possible errors that can be produced when writing a flight recording
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum FlightRecorderError {
+    /// An [IO](std::io) Error
+    #[error("Could not write flight recording: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+// state of recorded grid at time since recording started
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct FlightSample {
+    pub time: f32,
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub linvel: Vec3,
+    pub angvel: Vec3,
+    pub mass: f32,
+    pub throttle: f32,
+    pub fuel: f32,
+}
+
+// formats samples as csv rows with a header
+pub fn samples_to_csv(samples: &[FlightSample]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+    for s in samples.iter() {
+        let _ = writeln!(csv, "{:.3},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            s.time, s.position.x, s.position.y, s.position.z,
+            s.rotation.x, s.rotation.y, s.rotation.z, s.rotation.w,
+            s.linvel.x, s.linvel.y, s.linvel.z, s.angvel.x, s.angvel.y, s.angvel.z,
+            s.mass, s.throttle, s.fuel);
+    }
+    csv
+}
+
+// records samples of one grid at a fixed rate, written out when stopped
+#[derive(Resource)]
+pub struct FlightRecorder {
+    pub output_dir: PathBuf,
+    pub sample_interval: f32,
+    // start recording when the piloted grid begins thrusting
+    pub auto_record: bool,
+    grid: Option<Entity>,
+    samples: Vec<FlightSample>,
+    elapsed: f32,
+    next_sample: f32,
+    // wall clock start in ms since epoch, names the output file
+    #[cfg(not(target_arch = "wasm32"))]
+    started_at: u128,
+    // grid recording was auto started for, until its pilot leaves
+    launched: Option<Entity>,
+}
+
+impl Default for FlightRecorder {
+    fn default() -> Self {
+        Self {
+            output_dir: user_data_dir().join(FLIGHTS_DIR),
+            sample_interval: SAMPLE_INTERVAL,
+            auto_record: true,
+            grid: None,
+            samples: vec![],
+            elapsed: 0.,
+            next_sample: 0.,
+            #[cfg(not(target_arch = "wasm32"))]
+            started_at: 0,
+            launched: None,
+        }
+    }
+}
+
+impl FlightRecorder {
+    pub fn grid(&self) -> Option<Entity> {
+        self.grid
+    }
+
+    // starts a new recording of grid, discarding unsaved samples
+    pub fn start(&mut self, grid: Entity) {
+        self.grid = Some(grid);
+        self.samples.clear();
+        self.elapsed = 0.;
+        self.next_sample = 0.;
+        // std clock panics on web
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.started_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_millis());
+        }
+    }
+
+    // advances recording time by dt, returning time of sample if one is due
+    pub fn tick(&mut self, dt: f32) -> Option<f32> {
+        self.grid?;
+        let time = self.elapsed;
+        self.elapsed += dt;
+        if time < self.next_sample {
+            return None;
+        }
+        self.next_sample += self.sample_interval;
+        // skip samples missed during long frames
+        if self.next_sample <= time {
+            self.next_sample = time + self.sample_interval;
+        }
+        Some(time)
+    }
+
+    pub fn record(&mut self, sample: FlightSample) {
+        self.samples.push(sample);
+    }
+
+    // stops recording and writes samples to a timestamped file, if any were taken
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn stop(&mut self) -> Result<Option<PathBuf>, FlightRecorderError> {
+        if self.grid.take().is_none() || self.samples.is_empty() {
+            return Ok(None);
+        }
+        let samples = std::mem::take(&mut self.samples);
+        let path = self.output_dir.join(format!("flight_{}.csv", self.started_at));
+        write_csv(&path, &samples)?;
+        Ok(Some(path))
+    }
+
+    // stops recording, no file system on web so samples are discarded
+    #[cfg(target_arch = "wasm32")]
+    pub fn stop(&mut self) -> Result<Option<PathBuf>, FlightRecorderError> {
+        self.grid = None;
+        self.samples.clear();
+        Ok(None)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_csv(path: &std::path::Path, samples: &[FlightSample]) -> Result<(), FlightRecorderError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, samples_to_csv(samples))?;
+    Ok(())
+}
+
+fn log_stopped(stopped: Result<Option<PathBuf>, FlightRecorderError>) {
+    match stopped {
+        Ok(Some(path)) => info!("Saved flight recording: {:?}", path),
+        Ok(None) => {},
+        Err(err) => error!("Failed to save flight recording: {}", err),
+    }
+}
+
+/// Samples the recorded grid, needs no input or game state so it also runs in headless apps
+pub struct FlightRecorderPlugin;
+
+impl Plugin for FlightRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<FlightRecorder>()
+            .add_systems(Update, (
+                sample_flight_recorder.run_if(not(state_exists_and_equals(GameState::Paused))),));
+    }
+}
+
+/// Starts and stops recording of the piloted grid, from key input or on launch
+pub struct FlightRecorderControlsPlugin;
+
+impl Plugin for FlightRecorderControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                update_flight_recorder_controls.run_if(in_state(GameState::Running)),));
+    }
+}
+
+fn sample_flight_recorder(
+    time: Res<Time>,
+    mut recorder: ResMut<FlightRecorder>,
+    building_actions: Option<Res<BuildingActionsState>>,
+    origin: Option<Res<WorldOrigin>>,
+    tanks_query: Query<(&GridBlock, &FuelTank)>,
+    grids_query: Query<(&GlobalTransform, Option<&Velocity>, Option<&ReadMassProperties>)>,
+) {
+    let Some(grid_entity) = recorder.grid() else {
+        return;
+    };
+    // recording ends with its grid
+    let Ok((grid_gt, velocity, mass_props)) = grids_query.get(grid_entity) else {
+        log_stopped(recorder.stop());
+        return;
+    };
+    let Some(sample_time) = recorder.tick(time.delta_seconds()) else {
+        return;
+    };
+
+    let (_, rotation, _) = grid_gt.to_scale_rotation_translation();
+    let local_com = mass_props.map_or(Vec3::ZERO, |mp| mp.local_center_of_mass);
+    let velocity = velocity.copied().unwrap_or_default();
+    // absolute, unaffected by origin shifts during the flight
+    let position = grid_gt.transform_point(local_com);
+    recorder.record(FlightSample {
+        time: sample_time,
+        position: origin.map_or(position, |origin| origin.to_absolute(position)),
+        rotation,
+        linvel: velocity.linvel,
+        angvel: velocity.angvel,
+        mass: mass_props.map_or(0., |mp| mp.mass),
+        throttle: building_actions.map_or(0., |actions| throttle_percent(actions.thrusters_power)),
+        fuel: tanks_query.iter()
+            .filter(|(gb, _)| gb.entity == grid_entity)
+            .fold(0., |fuel, (_, tank)| fuel + tank.fuel),
+    });
+}
+
+fn update_flight_recorder_controls(
+    key_state: Res<KeyInputState>,
+    thrusters_state: Res<ThrustersState>,
+    mut recorder: ResMut<FlightRecorder>,
+    mover_query: Query<&MoverState>,
+    parents: Query<&Parent>,
+    blocks_query: Query<&GridBlock>,
+    grids_query: Query<Entity, With<Grid>>,
+) {
+    // grid of occupied flight seat
+    let piloted_grid = mover_query.iter()
+        .find_map(|mover_state| piloted_grid(mover_state, &parents, &blocks_query))
+        .filter(|grid_entity| grids_query.contains(*grid_entity));
+
+    if key_state.toggle_record {
+        if recorder.grid().is_some() {
+            log_stopped(recorder.stop());
+        } else if let Some(grid_entity) = piloted_grid {
+            recorder.start(grid_entity);
+        }
+        return;
+    }
+
+    // each launch is recorded once, until the pilot leaves the seat
+    match piloted_grid {
+        Some(grid_entity) => {
+            if recorder.auto_record && recorder.grid().is_none() && recorder.launched != Some(grid_entity) &&
+                thrusters_state.thrusting_grids.contains(&grid_entity) {
+                recorder.launched = Some(grid_entity);
+                recorder.start(grid_entity);
+            }
+        }
+        None => {
+            if recorder.launched.take().is_some_and(|launched| recorder.grid() == Some(launched)) {
+                log_stopped(recorder.stop());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    #[test]
+    fn headless_recording_to_csv() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlightRecorderPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(50)));
+        let grid = app.world.spawn((
+            GlobalTransform::from_xyz(1., 2., 3.),
+            Velocity::linear(Vec3::new(0., 4., 0.)),
+        )).id();
+        app.world.resource_mut::<FlightRecorder>().start(grid);
+
+        // 50 ms frames sample every 0.1 s
+        for _ in 0..5 {
+            app.update();
+        }
+        let recorder = app.world.resource::<FlightRecorder>();
+        assert_eq!(recorder.grid(), Some(grid));
+        let times: Vec<f32> = recorder.samples.iter().map(|sample| sample.time).collect();
+        assert_eq!(times.len(), 2);
+
+        let csv = samples_to_csv(&recorder.samples);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        let row: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(row.len(), CSV_HEADER.split(',').count());
+        assert_eq!(&row[1..4], ["1", "2", "3"]);
+        assert_eq!(row[9], "4");
+        assert_eq!(csv.lines().count(), 1 + times.len());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::actions::BuildingActionsState;
+use crate::building::{Grid,GridBlock};
+use crate::character::MoverState;
+use crate::game_state::GameState;
+use crate::inputs::KeyInputState;
+use crate::props::THRUSTERS_FULL_POWER;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+// thrusters power change per second while throttling
+const THROTTLE_RATE: f32 = 20.;
+// dampening torque command per unit of angular velocity
+const DAMPENING_GAIN: f32 = 2.;
+// max thruster gimbal angle
+pub const MAX_GIMBAL: f32 = 10. * std::f32::consts::PI / 180.;
+
+// system state
+#[derive(Default, Resource)]
+pub struct FlightState {
+    pub dampening: bool,
+}
+
+// pid controller holding the attitude of a grid, engaged by stability assist
+#[derive(Component,Clone,Debug)]
+pub struct AttitudeHold {
+    pub target: Quat,
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    integral: Vec3,
+}
+
+impl AttitudeHold {
+    pub fn new(target: Quat) -> Self {
+        Self { target, kp: 4., ki: 0.5, kd: 2., integral: Vec3::ZERO }
+    }
+
+    // torque command in world space turning rotation towards target,
+    // while driving angular velocity to zero
+    pub fn update(&mut self, rotation: Quat, angvel: Vec3, dt: f32) -> Vec3 {
+        // shortest rotation from current to target, as scaled axis
+        let mut error = self.target * rotation.inverse();
+        if error.w < 0. {
+            error = -error;
+        }
+        let (axis, angle) = error.to_axis_angle();
+        let error = if angle > 1E-6 { axis * angle } else { Vec3::ZERO };
+
+        self.integral = (self.integral + error * dt).clamp_length_max(1.);
+        (self.kp * error + self.ki * self.integral - self.kd * angvel).clamp_length_max(1.)
+    }
+}
+
+// control input of piloted grid
+#[derive(Component,Clone,Debug,Default)]
+pub struct FlightControl {
+    // desired torque axis in world space, scaled by command strength up to 1
+    pub torque_command: Vec3,
+}
+
+// tilts thrust up to the max gimbal angle towards producing torque along command,
+// thrust at offset from center of mass can only turn the grid perpendicular to both
+pub fn gimbal_thrust(
+    thrust: Vec3,
+    offset: Vec3,
+    torque_command: Vec3,
+    max_gimbal: f32,
+) -> Vec3 {
+    let strength = torque_command.length().min(1.);
+    let direction = thrust.normalize_or_zero();
+    let lever = torque_command.cross(offset);
+    let deflection = lever - direction * lever.dot(direction);
+    if strength <= 0. || deflection.length_squared() < 1E-6 {
+        return thrust;
+    }
+    let deflected = direction + deflection.normalize() * (max_gimbal * strength).tan();
+    deflected.normalize() * thrust.length()
+}
+
+pub struct FlightStatePlugin;
+
+impl Plugin for FlightStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(FlightState::default())
+            .add_systems(Update, (
+                update_flight_state.run_if(in_state(GameState::Running)),));
+    }
+}
+
+// seat block and grid of occupied flight seat
+pub fn piloted_seat(
+    mover_state: &MoverState,
+    parents: &Query<&Parent>,
+    blocks_query: &Query<&GridBlock>,
+) -> Option<(Entity, Entity)> {
+    let seat_block = parents.get(mover_state.seated_in?).ok()?.get();
+    blocks_query.get(seat_block).ok().map(|gb| (seat_block, gb.entity))
+}
+
+// grid of occupied flight seat
+pub fn piloted_grid(
+    mover_state: &MoverState,
+    parents: &Query<&Parent>,
+    blocks_query: &Query<&GridBlock>,
+) -> Option<Entity> {
+    piloted_seat(mover_state, parents, blocks_query).map(|(_, grid_entity)| grid_entity)
+}
+
+// routes seated input to the grid of the occupied flight seat
+fn update_flight_state(
+    mut commands: Commands,
+    time: Res<Time>,
+    key_state: Res<KeyInputState>,
+    mut flight_state: ResMut<FlightState>,
+    mut building_actions: ResMut<BuildingActionsState>,
+    mover_query: Query<&MoverState>,
+    parents: Query<&Parent>,
+    blocks_query: Query<&GridBlock>,
+    seats_query: Query<&GlobalTransform, With<GridBlock>>,
+    mut grids_query: Query<(Entity, &GlobalTransform, Option<&Velocity>,
+        Option<&FlightControl>, Option<&mut AttitudeHold>), With<Grid>>,
+) {
+    let piloted = mover_query.iter()
+        .find_map(|mover_state| piloted_seat(mover_state, &parents, &blocks_query));
+    let piloted_grid = piloted.map(|(_, grid_entity)| grid_entity);
+
+    // release grids no longer piloted
+    for (grid_entity, _, _, flight_control, _) in grids_query.iter() {
+        if flight_control.is_some() && Some(grid_entity) != piloted_grid {
+            commands.entity(grid_entity).remove::<FlightControl>();
+        }
+    }
+    let Some((seat_gt, grid_entity)) = piloted
+        .and_then(|(seat_block, grid_entity)| seats_query.get(seat_block).ok().map(|seat_gt| (seat_gt, grid_entity))) else {
+        return;
+    };
+    let Ok((grid_entity, grid_gt, velocity, _, attitude_hold)) = grids_query.get_mut(grid_entity) else {
+        return;
+    };
+    let (_, grid_rotation, _) = grid_gt.to_scale_rotation_translation();
+    let angvel = velocity.map_or(Vec3::ZERO, |velocity| velocity.angvel);
+
+    if key_state.toggle_dampening {
+        flight_state.dampening = !flight_state.dampening;
+    }
+
+    // throttle, between thrusters idle and full power
+    let idle_power = BuildingActionsState::default().thrusters_power;
+    if key_state.throttle != 0. {
+        building_actions.thrusters_power = (building_actions.thrusters_power +
+            key_state.throttle * THROTTLE_RATE * time.delta_seconds()).clamp(idle_power, THRUSTERS_FULL_POWER);
+    }
+
+    // pitch, yaw and roll about seat axes
+    let axis_input = |positive: bool, negative: bool| (positive as i32 - negative as i32) as f32;
+    let seat_input = Vec3::new(
+        -key_state.movement.y,
+        -key_state.movement.x,
+        axis_input(key_state.roll_left, key_state.roll_right),
+    );
+    let (_, seat_rotation, _) = seat_gt.to_scale_rotation_translation();
+    let mut torque_command = seat_rotation.mul_vec3(seat_input);
+
+    // stability assist holds attitude while there is no manual input
+    if key_state.toggle_sas {
+        if attitude_hold.is_some() {
+            commands.entity(grid_entity).remove::<AttitudeHold>();
+        } else {
+            commands.entity(grid_entity).insert(AttitudeHold::new(grid_rotation));
+        }
+    }
+    if let Some(mut attitude_hold) = attitude_hold {
+        if seat_input != Vec3::ZERO {
+            attitude_hold.target = grid_rotation;
+        } else {
+            torque_command = attitude_hold.update(grid_rotation, angvel, time.delta_seconds());
+        }
+    }
+    if flight_state.dampening {
+        torque_command -= DAMPENING_GAIN * angvel;
+    }
+
+    commands.entity(grid_entity).insert(FlightControl {
+        torque_command: torque_command.clamp_length_max(1.),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attitude_hold_settles_on_target() {
+        let target = Quat::from_rotation_x(0.3);
+        let mut hold = AttitudeHold::new(target);
+        let mut rotation = Quat::from_axis_angle(Vec3::new(1., 1., 0.).normalize(), 1.2) * target;
+        let mut angvel = Vec3::new(0., 0.5, -0.3);
+
+        // unit inertia body, torque command as angular acceleration
+        let dt = 1. / 60.;
+        for _ in 0..(20. / dt) as usize {
+            angvel += hold.update(rotation, angvel, dt) * dt;
+            rotation = (Quat::from_scaled_axis(angvel * dt) * rotation).normalize();
+        }
+        assert!(angvel.length() < 1E-2, "angular velocity {angvel}");
+        assert!(rotation.angle_between(target) < 1E-2, "attitude error {}", rotation.angle_between(target));
+    }
+
+    #[test]
+    fn gimbal_torque_follows_command() {
+        // engine below the center of mass, thrusting up
+        let thrust = Vec3::new(0., 10., 0.);
+        let offset = Vec3::new(0., -2., 0.);
+        for torque_command in [Vec3::X, Vec3::NEG_X, Vec3::Z, Vec3::new(0., 0., -0.5)] {
+            let gimballed = gimbal_thrust(thrust, offset, torque_command, MAX_GIMBAL);
+            assert!(offset.cross(gimballed).dot(torque_command) > 0., "{torque_command} -> {gimballed}");
+            assert!((gimballed.length() - thrust.length()).abs() < 1E-4);
+            assert!(gimballed.angle_between(thrust) <= MAX_GIMBAL + 1E-4);
+        }
+        // roll can't be produced by thrust along the offset
+        assert_eq!(gimbal_thrust(thrust, offset, Vec3::Y, MAX_GIMBAL), thrust);
+        assert_eq!(gimbal_thrust(thrust, offset, Vec3::ZERO, MAX_GIMBAL), thrust);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::building::{Grid,GridBlock};
+use crate::game_state::GameState;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+
+// mass per unit of fuel
+pub const FUEL_DENSITY: f32 = 0.01;
+
+#[derive(Component,Clone,Debug,Default)]
+pub struct FuelTank {
+    pub fuel: f32,
+    pub capacity: f32,
+}
+
+// draws amount of fuel from tanks in order, returning amount drawn
+pub fn draw_fuel<'a>(
+    tanks: impl Iterator<Item = &'a mut FuelTank>,
+    amount: f32,
+) -> f32 {
+    let mut remaining = amount;
+    for tank in tanks {
+        if remaining <= 0. {
+            break;
+        }
+        let drawn = tank.fuel.min(remaining);
+        tank.fuel -= drawn;
+        remaining -= drawn;
+    }
+    amount - remaining
+}
+
+pub struct FuelStatePlugin;
+
+impl Plugin for FuelStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                update_fuel_mass.run_if(in_state(GameState::Running)),));
+    }
+}
+
+// adds mass of remaining fuel onto parent grids, centered on their tanks
+fn update_fuel_mass(
+    mut commands: Commands,
+    tanks_query: Query<(&GridBlock, &FuelTank)>,
+    grids_query: Query<(Entity, Option<&AdditionalMassProperties>), With<Grid>>,
+) {
+    let mut grid_fuel: HashMap<Entity, (f32, Vec3)> = HashMap::new();
+    for (gb, tank) in tanks_query.iter() {
+        let Some((translation, _, _)) = gb.collider.as_ref() else {
+            continue;
+        };
+        let fuel_mass = tank.fuel * FUEL_DENSITY;
+        let (mass, moment) = grid_fuel.entry(gb.entity).or_default();
+        *mass += fuel_mass;
+        *moment += *translation * fuel_mass;
+    }
+
+    for (grid_entity, current) in grids_query.iter() {
+        let (mass, moment) = grid_fuel.get(&grid_entity).copied().unwrap_or_default();
+        let mass_props = if mass > 0. {
+            MassProperties { local_center_of_mass: moment / mass, mass, ..default() }
+        } else {
+            MassProperties::default()
+        };
+        let next = AdditionalMassProperties::MassProperties(mass_props);
+        if current != Some(&next) {
+            commands.entity(grid_entity).insert(next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tanks(fuel: &[f32]) -> Vec<FuelTank> {
+        fuel.iter().map(|&fuel| FuelTank { fuel, capacity: 10. }).collect()
+    }
+
+    #[test]
+    fn draws_tanks_in_order() {
+        let mut tanks = tanks(&[3., 10.]);
+        assert_eq!(draw_fuel(tanks.iter_mut(), 5.), 5.);
+        assert_eq!(tanks[0].fuel, 0.);
+        assert_eq!(tanks[1].fuel, 8.);
+    }
+
+    #[test]
+    fn starved_draw_returns_what_is_left() {
+        let mut tanks = tanks(&[1., 2.]);
+        assert_eq!(draw_fuel(tanks.iter_mut(), 5.), 3.);
+        assert!(tanks.iter().all(|tank| tank.fuel == 0.));
+        assert_eq!(draw_fuel(tanks.iter_mut(), 5.), 0.);
+    }
+}
@@ -1,6 +1,20 @@
+mod aero_state;
+pub use aero_state::*;
+mod flight_recorder;
+pub use flight_recorder::*;
+mod flight_state;
+pub use flight_state::*;
+mod fuel_state;
+pub use fuel_state::*;
 mod interactables_states;
 pub use interactables_states::*;
 mod props_states_plugin;
 pub use props_states_plugin::*;
+mod reaction_wheel_state;
+pub use reaction_wheel_state::*;
+mod staging_state;
+pub use staging_state::*;
+mod telemetry_state;
+pub use telemetry_state::*;
 mod thrusters_state;
 pub use thrusters_state::*;
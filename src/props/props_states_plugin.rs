@@ -1,5 +1,13 @@
 use bevy::prelude::*;
 
+use crate::props::AeroStatePlugin;
+use crate::props::FlightRecorderControlsPlugin;
+use crate::props::FlightRecorderPlugin;
+use crate::props::FlightStatePlugin;
+use crate::props::FuelStatePlugin;
+use crate::props::ReactionWheelStatePlugin;
+use crate::props::StagingStatePlugin;
+use crate::props::TelemetryStatePlugin;
 use crate::props::ThrustersStatePlugin;
 use crate::props::InteractablesStatePlugin;
 
@@ -11,6 +19,13 @@ impl Plugin for PropsStatesPlugin {
     fn build(&self, app: &mut App) {
         app
         .add_plugins((InteractablesStatePlugin::default(),))
-        .add_plugins((ThrustersStatePlugin::default(),));
+        .add_plugins((ThrustersStatePlugin::default(),))
+        .add_plugins((FuelStatePlugin,))
+        .add_plugins((StagingStatePlugin,))
+        .add_plugins((AeroStatePlugin,))
+        .add_plugins((FlightStatePlugin,))
+        .add_plugins((ReactionWheelStatePlugin,))
+        .add_plugins((TelemetryStatePlugin,))
+        .add_plugins((FlightRecorderPlugin, FlightRecorderControlsPlugin));
     }
 }
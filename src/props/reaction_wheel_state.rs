@@ -0,0 +1,42 @@
+use crate::building::{BpInfos,GridBlock};
+use crate::game_state::GameState;
+use crate::props::FlightControl;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+
+pub struct ReactionWheelStatePlugin;
+
+impl Plugin for ReactionWheelStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                update_reaction_wheel_state.run_if(in_state(GameState::Running)),));
+    }
+}
+
+// turns piloted grids along their torque command, bounded by torque of their reaction wheels
+fn update_reaction_wheel_state(
+    time: Res<Time>,
+    infos: Res<BpInfos>,
+    blocks_query: Query<&GridBlock>,
+    mut grids_query: Query<(Entity, &FlightControl, &mut ExternalImpulse)>,
+) {
+    let mut grid_torque: HashMap<Entity, f32> = HashMap::new();
+    for gb in blocks_query.iter() {
+        let Some(bp_info) = infos.bps.get(&gb.bp_name) else {
+            continue;
+        };
+        if bp_info.torque > 0. {
+            *grid_torque.entry(gb.entity).or_default() += bp_info.torque;
+        }
+    }
+
+    for (grid_entity, flight_control, mut impulse) in grids_query.iter_mut() {
+        let Some(max_torque) = grid_torque.get(&grid_entity) else {
+            continue;
+        };
+        let torque = flight_control.torque_command.clamp_length_max(1.) * *max_torque;
+        impulse.torque_impulse += torque * time.delta_seconds();
+    }
+}
@@ -0,0 +1,109 @@
+use crate::building::{BlockSettings,BpInfos,BpSnapsEvent,Grid,GridBlock,remove_grid_blocks};
+use crate::character::MoverState;
+use crate::game_state::GameState;
+use crate::inputs::KeyInputState;
+use crate::props::{piloted_grid,ThrusterInteractable};
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+pub const DECOUPLER_PART: &str = "decoupler";
+
+// Tag for decoupler blocks, fired when their stage is triggered
+#[derive(Component,Default)]
+pub struct Decoupler {
+    pub stage: u32,
+}
+
+// parts which are assigned a stage when built
+pub fn is_staged_part(bp_name: &str) -> bool {
+    bp_name == "thruster" || bp_name == DECOUPLER_PART
+}
+
+// stage of block from its settings, defaulting to launch stage
+pub fn block_stage(settings: Option<&BlockSettings>) -> u32 {
+    settings.and_then(|s| s.0.get("stage")).map_or(0, |stage| stage.max(0.) as u32)
+}
+
+// next stage is the lowest one still pending, of unfired decouplers and inactive thrusters
+pub fn next_stage(
+    decoupler_stages: impl Iterator<Item = u32>,
+    inactive_thruster_stages: impl Iterator<Item = u32>,
+) -> Option<u32> {
+    decoupler_stages.chain(inactive_thruster_stages).min()
+}
+
+pub struct StagingStatePlugin;
+
+impl Plugin for StagingStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                update_staging_state.run_if(in_state(GameState::Running)),));
+    }
+}
+
+// triggers next stage of seated grid, firing its decouplers and activating its thrusters
+fn update_staging_state(
+    mut commands: Commands,
+    key_state: Res<KeyInputState>,
+    infos: Res<BpInfos>,
+    mover_query: Query<&MoverState>,
+    parents: Query<&Parent>,
+    blocks_query: Query<&GridBlock>,
+    gb_query: Query<(Entity, &GridBlock)>,
+    decouplers_query: Query<(Entity, &GridBlock, &Decoupler)>,
+    mut thrusters_query: Query<(&GridBlock, &mut ThrusterInteractable)>,
+    grids_query: Query<(&Transform, Option<&Velocity>, Option<&ReadMassProperties>), With<Grid>>,
+    mut snaps_events: EventWriter<BpSnapsEvent>,
+) {
+    if !key_state.jump {
+        return;
+    }
+
+    // grid of occupied flight seat
+    let Some(grid_entity) = mover_query.iter()
+        .find_map(|mover_state| piloted_grid(mover_state, &parents, &blocks_query)) else {
+        return;
+    };
+
+    let next_stage = next_stage(
+        decouplers_query.iter()
+            .filter(|(_, gb, _)| gb.entity == grid_entity)
+            .map(|(_, _, decoupler)| decoupler.stage),
+        thrusters_query.iter()
+            .filter(|(gb, ti)| gb.entity == grid_entity && !ti.active)
+            .map(|(_, ti)| ti.stage));
+    let Some(next_stage) = next_stage else {
+        return;
+    };
+
+    for (gb, mut ti) in thrusters_query.iter_mut() {
+        if gb.entity == grid_entity && ti.stage == next_stage {
+            ti.active = true;
+        }
+    }
+
+    // removing decouplers splits grid into separate bodies
+    let fired: Vec<Entity> = decouplers_query.iter()
+        .filter(|(_, gb, decoupler)| gb.entity == grid_entity && decoupler.stage == next_stage)
+        .map(|(entity, _, _)| entity)
+        .collect();
+    if !fired.is_empty() {
+        remove_grid_blocks(&mut commands, grid_entity, &fired,
+            &infos, &grids_query, &gb_query, &mut snaps_events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_stage_is_lowest_pending() {
+        assert_eq!(next_stage([2, 1].into_iter(), [3].into_iter()), Some(1));
+        assert_eq!(next_stage([2].into_iter(), [0, 4].into_iter()), Some(0));
+        // all thrusters burning and decouplers fired
+        assert_eq!(next_stage(std::iter::empty(), std::iter::empty()), None);
+    }
+}
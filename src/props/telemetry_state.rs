@@ -0,0 +1,191 @@
+use crate::actions::BuildingActionsState;
+use crate::building::{Grid,GridBlock};
+use crate::character::MoverState;
+use crate::game_state::GameState;
+use crate::loading::FontAssets;
+use crate::props::{FuelTank,piloted_grid,throttle_percent};
+use crate::world::{ActiveTerrain,WorldOrigin,terrain_height};
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+// system state
+#[derive(Default, Resource)]
+pub struct TelemetryState {
+    pub ui_entity: Option<Entity>,
+    // grid and its velocity last update, for acceleration
+    last_velocity: Option<(Entity, Vec3)>,
+}
+
+// Tag for UI component
+#[derive(Component)]
+struct TelemetryText;
+
+// readouts of piloted grid
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct Telemetry {
+    pub altitude_terrain: f32,
+    pub altitude: f32,
+    pub vertical_speed: f32,
+    pub horizontal_speed: f32,
+    pub g_load: f32,
+    // percent of full thrusters power
+    pub throttle: f32,
+    // remaining fuel and capacity of grid tanks
+    pub fuel: f32,
+    pub fuel_capacity: f32,
+    pub thrust_to_weight: f32,
+}
+
+impl Telemetry {
+    pub fn to_text(&self) -> String {
+        let fuel = if self.fuel_capacity > 0. {
+            format!("{:.0} ({:.0}%)", self.fuel, 100. * self.fuel / self.fuel_capacity)
+        } else {
+            "-".into()
+        };
+        format!("ALT {:.1} m  (AGL {:.1} m)\nV/S {:.1} m/s\nH/S {:.1} m/s\nG {:.2}\nTHR {:.0}%\nFUEL {}\nTWR {:.2}",
+            self.altitude, self.altitude_terrain, self.vertical_speed, self.horizontal_speed,
+            self.g_load, self.throttle, fuel, self.thrust_to_weight)
+    }
+}
+
+// felt acceleration in multiples of gravity, from change of velocity over dt
+pub fn g_load(last_velocity: Vec3, velocity: Vec3, dt: f32, gravity: Vec3) -> f32 {
+    let g = gravity.length();
+    if dt <= 0. || g <= 0. {
+        return 0.;
+    }
+    ((velocity - last_velocity) / dt - gravity).length() / g
+}
+
+pub struct TelemetryStatePlugin;
+
+impl Plugin for TelemetryStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(TelemetryState::default())
+            .add_systems(OnEnter(GameState::Running), setup_telemetry_state)
+            .add_systems(Update, (
+                update_telemetry_state.run_if(in_state(GameState::Running)),))
+            .add_systems(OnExit(GameState::Running), exit_telemetry_state);
+    }
+}
+
+fn setup_telemetry_state(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    mut telemetry_state: ResMut<TelemetryState>,
+) {
+    telemetry_state.last_velocity = None;
+    telemetry_state.ui_entity = Some(commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.),
+                top: Val::Px(10.),
+                padding: UiRect::all(Val::Px(6.)),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.3).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: Text {
+                    sections: vec![TextSection {
+                        value: " ".to_string(),
+                        style: TextStyle {
+                            font: font_assets.fira_sans.clone(),
+                            font_size: 14.0,
+                            color: Color::rgba(0.9, 0.9, 0.9, 0.8),
+                        },
+                    }],
+                    linebreak_behavior: bevy::text::BreakLineOn::NoWrap,
+                    alignment: TextAlignment::Left,
+                },
+                ..default()
+            }).insert(TelemetryText);
+        })
+    .id());
+}
+
+// shows readouts of grid of occupied flight seat
+fn update_telemetry_state(
+    time: Res<Time>,
+    rapier_config: Res<RapierConfiguration>,
+    origin: Res<WorldOrigin>,
+    terrain: Res<ActiveTerrain>,
+    building_actions: Res<BuildingActionsState>,
+    mut telemetry_state: ResMut<TelemetryState>,
+    mover_query: Query<&MoverState>,
+    parents: Query<&Parent>,
+    blocks_query: Query<&GridBlock>,
+    tanks_query: Query<(&GridBlock, &FuelTank)>,
+    grids_query: Query<(&GlobalTransform, &Velocity, Option<&ReadMassProperties>, Option<&ExternalForce>), With<Grid>>,
+    mut visibility_query: Query<&mut Visibility>,
+    mut text_query: Query<&mut Text, With<TelemetryText>>,
+) {
+    let Some(ui_entity) = telemetry_state.ui_entity else {
+        return;
+    };
+
+    // grid of occupied flight seat
+    let piloted_grid = mover_query.iter()
+        .find_map(|mover_state| piloted_grid(mover_state, &parents, &blocks_query))
+        .and_then(|grid_entity| grids_query.get(grid_entity).ok().map(|grid| (grid_entity, grid)));
+
+    let visibility = if piloted_grid.is_some() { Visibility::Inherited } else { Visibility::Hidden };
+    if let Ok(mut ui_visibility) = visibility_query.get_mut(ui_entity) {
+        if *ui_visibility != visibility {
+            *ui_visibility = visibility;
+        }
+    }
+    let Some((grid_entity, (grid_gt, velocity, mass_props, ext_force))) = piloted_grid else {
+        telemetry_state.last_velocity = None;
+        return;
+    };
+
+    let local_com = mass_props.map_or(Vec3::ZERO, |mp| mp.local_center_of_mass);
+    let position = origin.to_absolute(grid_gt.transform_point(local_com));
+    let gravity = rapier_config.gravity;
+    let weight = mass_props.map_or(0., |mp| mp.mass) * gravity.length();
+    let thrust = ext_force.map_or(0., |ext_force| ext_force.force.length());
+    let (fuel, fuel_capacity) = tanks_query.iter()
+        .filter(|(gb, _)| gb.entity == grid_entity)
+        .fold((0., 0.), |(fuel, capacity), (_, tank)| (fuel + tank.fuel, capacity + tank.capacity));
+    let load = match telemetry_state.last_velocity {
+        Some((last_grid, last_velocity)) if last_grid == grid_entity =>
+            g_load(last_velocity, velocity.linvel, time.delta_seconds(), gravity),
+        _ => 0.,
+    };
+    telemetry_state.last_velocity = Some((grid_entity, velocity.linvel));
+
+    let telemetry = Telemetry {
+        altitude_terrain: position.y - terrain_height(&*terrain.generator, position.x, position.z),
+        altitude: position.y,
+        vertical_speed: velocity.linvel.y,
+        horizontal_speed: Vec2::new(velocity.linvel.x, velocity.linvel.z).length(),
+        g_load: load,
+        throttle: throttle_percent(building_actions.thrusters_power),
+        fuel,
+        fuel_capacity,
+        thrust_to_weight: if weight > 0. { thrust / weight } else { 0. },
+    };
+    let status = telemetry.to_text();
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+}
+
+fn exit_telemetry_state(
+    mut commands: Commands,
+    mut telemetry_state: ResMut<TelemetryState>,
+) {
+    if let Some(ui_entity) = telemetry_state.ui_entity.take() {
+        commands.entity(ui_entity).despawn_recursive();
+    }
+}
@@ -1,27 +1,43 @@
 use crate::actions::BuildingActionsState;
-use crate::building::ThrustersStatusText;
+use crate::building::{BpInfos,DiscreteVec3,GridBlock,ThrustersStatusText,grid_islands};
 use crate::game_state::GameState;
 use crate::loading::TextureAssets;
+use crate::props::{FlightControl,FuelTank,draw_fuel,gimbal_thrust,DECOUPLER_PART,MAX_GIMBAL};
+use crate::world::{Atmosphere,thrust_at_pressure};
 
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
 use bevy_rapier3d::prelude::*;
 
 use std::f32::consts::PI;
 
 const THRUSTERS_CUTOFF: f32 = 12.;
+// thrusters power at full throttle
+pub const THRUSTERS_FULL_POWER: f32 = 100.;
+
+// thrusters power as percent of full throttle, for display and recording
+pub fn throttle_percent(thrusters_power: f32) -> f32 {
+    (100. * thrusters_power / THRUSTERS_FULL_POWER).clamp(0., 100.)
+}
 
 // system state
 #[derive(Default, Resource)]
 pub struct ThrustersState {
     pub thrusters_power: f32,
     pub thrusters_animating: bool,
+    // grids with thrust applied last update
+    pub thrusting_grids: Vec<Entity>,
 }
 
 
 #[derive(Component,Default)]
 pub struct ThrusterInteractable {
-    pub grid: Option<Entity>,
+    // stage activating thruster, stage 0 fires from launch
+    pub stage: u32,
+    pub active: bool,
 }
+// Tag for thrusters with combustion sprites
+#[derive(Component)]
+struct ThrusterLit;
 #[derive(Component,Default)]
 pub struct ThrusterSprite {
 }
@@ -44,6 +60,21 @@ impl Plugin for ThrustersStatePlugin {
 #[derive(Component, Deref, DerefMut)]
 struct AnimationTimer(Timer);
 
+// sums force and torque about center of mass of thrusters at world position,
+// each pushing along its direction with given power
+pub fn sum_thruster_forces(
+    center_of_mass: Vec3,
+    thrusters: &[(Vec3, Vec3)],
+    power: f32,
+) -> ExternalForce {
+    thrusters.iter().fold(ExternalForce::default(), |mut sum, (position, direction)| {
+        let force = *direction * power;
+        sum.force += force;
+        sum.torque += (*position - center_of_mass).cross(force);
+        sum
+    })
+}
+
 fn create_thruster_sprite(
     commands: &mut Commands,
     texture_handles: &Res<TextureAssets>,
@@ -117,26 +148,33 @@ fn create_thruster_sprite(
 
 fn update_thursters_state(
     mut commands: Commands,
-    // time: Res<Time>,
+    time: Res<Time>,
+    infos: Res<BpInfos>,
+    atmosphere: Res<Atmosphere>,
     building_actions: Res<BuildingActionsState>,
     mut thrusters_state: ResMut<ThrustersState>,
-    thursters_query: Query<(Entity, &ThrusterInteractable, &GlobalTransform)>,
+    thursters_query: Query<(Entity, &GridBlock, &GlobalTransform, &ThrusterInteractable)>,
+    lit_query: Query<Entity, With<ThrusterLit>>,
+    blocks_query: Query<&GridBlock>,
+    mut tanks_query: Query<(&GridBlock, &mut FuelTank)>,
     thurster_sprites_query: Query<(Entity, &ThrusterSprite)>,
     texture_handles: Res<TextureAssets>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut ext_forces: Query<&mut ExternalForce>,
+    mut grids_query: Query<(&mut ExternalForce, &GlobalTransform, Option<&ReadMassProperties>, Option<&FlightControl>)>,
     mut toolbar_text: Query<(&ThrustersStatusText, &mut Text)>,
 ) {
     if building_actions.thrusters_power < THRUSTERS_CUTOFF {
         if thrusters_state.thrusters_animating {
             // cleanup sprites
             thurster_sprites_query.for_each(|ts| { commands.entity(ts.0).despawn_recursive(); });
-            // remove external force from parent grid
-            thursters_query.for_each(|t| { 
-                let mut ext_force = ext_forces.get_mut(t.1.grid.unwrap()).unwrap();
-                ext_force.force = Vec3::ZERO;
-            });
+            lit_query.for_each(|entity| { commands.entity(entity).remove::<ThrusterLit>(); });
+            // remove external force from parent grids
+            for grid_entity in thrusters_state.thrusting_grids.drain(..) {
+                if let Ok((mut ext_force, _, _, _)) = grids_query.get_mut(grid_entity) {
+                    *ext_force = ExternalForce::default();
+                }
+            }
             for (_, mut text) in toolbar_text.iter_mut() {
                 text.sections[0].value = " ".into();
             }
@@ -147,23 +185,160 @@ fn update_thursters_state(
 
     if (building_actions.thrusters_power - thrusters_state.thrusters_power).abs() > 0.001 {
         thrusters_state.thrusters_power = building_actions.thrusters_power;
-        thursters_query.for_each(|(_entity, ti, thruster_gt )| {
-            // add external force onto parent grid
-            let mut ext_force = ext_forces.get_mut(ti.grid.unwrap()).unwrap();
-            ext_force.force = thruster_gt.up() * thrusters_state.thrusters_power;
-        });
-        for (_, mut text) in toolbar_text.iter_mut() {
-            text.sections[0].value = format!("{:.0}%", thrusters_state.thrusters_power).into();
+    }
+
+    // group active thrusters by parent grid, in world space
+    let mut grid_thrusters: HashMap<Entity, Vec<(&GridBlock, Vec3, Vec3)>> = HashMap::new();
+    thursters_query.iter().filter(|(_, _, _, ti)| ti.active).for_each(|(_entity, gb, thruster_gt, _ti)| {
+        grid_thrusters.entry(gb.entity).or_default()
+            .push((gb, thruster_gt.translation(), thruster_gt.up()));
+    });
+
+    // fuel tanks of thrusting grids
+    let mut tanks: Vec<(Entity, DiscreteVec3, Mut<FuelTank>)> = tanks_query.iter_mut()
+        .filter(|(gb, _)| grid_thrusters.contains_key(&gb.entity))
+        .filter_map(|(gb, tank)| {
+            let (translation, _, _) = gb.collider.as_ref()?;
+            Some((gb.entity, DiscreteVec3::from(*translation), tank))
+        })
+        .collect();
+
+    // burn fuel, thrust scaled down when starved
+    let throttle = thrusters_state.thrusters_power / THRUSTERS_FULL_POWER;
+    let mut grid_thrust: HashMap<Entity, Vec<(Vec3, Vec3)>> = HashMap::new();
+    for (grid_entity, thrusters) in grid_thrusters.iter() {
+        // fuel flows between face-connected blocks, but not through decouplers
+        let positions: Vec<DiscreteVec3> = blocks_query.iter()
+            .filter(|gb| gb.entity == *grid_entity && gb.bp_name != DECOUPLER_PART)
+            .filter_map(|gb| gb.collider.as_ref().map(|c| DiscreteVec3::from(c.0)))
+            .collect();
+        let mut island_of: HashMap<DiscreteVec3, usize> = HashMap::new();
+        for (island_idx, island) in grid_islands(&positions).iter().enumerate() {
+            for &idx in island.iter() {
+                island_of.insert(positions[idx].clone(), island_idx);
+            }
+        }
+
+        let thrust = grid_thrust.entry(*grid_entity).or_default();
+        for (gb, position, direction) in thrusters.iter() {
+            let Some(bp_info) = infos.bps.get(&gb.bp_name) else {
+                continue;
+            };
+            let consumption = bp_info.fuel_consumption;
+            let demand = consumption * throttle * time.delta_seconds();
+            let fueled = if demand > 0. {
+                let island = gb.collider.as_ref().and_then(|c| island_of.get(&DiscreteVec3::from(c.0)));
+                let connected_tanks = tanks.iter_mut()
+                    .filter(|(tank_grid, tank_pos, tank)| {
+                        tank_grid == grid_entity && tank.fuel > 0. &&
+                            island.is_some() && island_of.get(tank_pos) == island
+                    })
+                    .map(|(_, _, tank)| tank.as_mut());
+                draw_fuel(connected_tanks, demand) / demand
+            } else {
+                1.
+            };
+            // thrusters perform better in thin air
+            let output = thrust_at_pressure(bp_info.thrust_sea_level, bp_info.thrust_vacuum,
+                atmosphere.pressure(position.y) / atmosphere.sea_level_pressure);
+            thrust.push((*position, *direction * fueled * output));
         }
     }
-    
-    if !thrusters_state.thrusters_animating {
-        thursters_query.for_each(|(entity, _ti, _thruster_gt )| {
-            // setup combustion sprites
-            create_thruster_sprite(&mut commands, &texture_handles, &mut meshes, &mut materials, entity);
-        });
-        thrusters_state.thrusters_animating = true;
+
+    // show power and remaining fuel of thrusting grids
+    let (fuel, capacity) = tanks.iter()
+        .fold((0., 0.), |(fuel, capacity), (_, _, tank)| (fuel + tank.fuel, capacity + tank.capacity));
+    let status = if capacity > 0. {
+        format!("{:.0}%  Fuel {:.0}%", thrusters_state.thrusters_power, 100. * fuel / capacity)
+    } else {
+        format!("{:.0}%", thrusters_state.thrusters_power)
+    };
+    for (_, mut text) in toolbar_text.iter_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+
+    // clear force of grids which lost their thrusters
+    for grid_entity in thrusters_state.thrusting_grids.iter() {
+        if !grid_thrusters.contains_key(grid_entity) {
+            if let Ok((mut ext_force, _, _, _)) = grids_query.get_mut(*grid_entity) {
+                *ext_force = ExternalForce::default();
+            }
+        }
     }
 
+    // apply summed force and torque about center of mass onto parent grids
+    thrusters_state.thrusting_grids.clear();
+    for (grid_entity, thrusters) in grid_thrust.iter() {
+        if let Ok((mut ext_force, grid_gt, mass_props, flight_control)) = grids_query.get_mut(*grid_entity) {
+            let local_com = mass_props.map_or(Vec3::ZERO, |mp| mp.local_center_of_mass);
+            let center_of_mass = grid_gt.transform_point(local_com);
+            // piloted grids gimbal thrusters to steer
+            let thrusters: Vec<(Vec3, Vec3)> = match flight_control {
+                Some(flight_control) => thrusters.iter().map(|(position, thrust)| {
+                    (*position, gimbal_thrust(*thrust, *position - center_of_mass,
+                        flight_control.torque_command, MAX_GIMBAL))
+                }).collect(),
+                None => thrusters.clone(),
+            };
+            *ext_force = sum_thruster_forces(
+                center_of_mass, &thrusters, thrusters_state.thrusters_power);
+            thrusters_state.thrusting_grids.push(*grid_entity);
+        }
+    }
+
+    // setup combustion sprites of newly active thrusters
+    thursters_query.for_each(|(entity, _gb, _thruster_gt, ti)| {
+        if ti.active && !lit_query.contains(entity) {
+            create_thruster_sprite(&mut commands, &texture_handles, &mut meshes, &mut materials, entity);
+            commands.entity(entity).insert(ThrusterLit);
+        }
+    });
+    thrusters_state.thrusters_animating = true;
+
     // TODO animate sprites...
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Vec3, expected: Vec3) {
+        assert!(actual.distance(expected) < 1E-4, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn symmetric_pair_cancels_torque() {
+        let com = Vec3::new(0., 1., 0.);
+        let thrusters = [(Vec3::new(-1., 0., 0.), Vec3::Y), (Vec3::new(1., 0., 0.), Vec3::Y)];
+        let sum = sum_thruster_forces(com, &thrusters, 10.);
+        assert_near(sum.force, Vec3::new(0., 20., 0.));
+        assert_near(sum.torque, Vec3::ZERO);
+    }
+
+    #[test]
+    fn off_centre_thruster_pitches() {
+        // r = (0, 0, 2), F = (0, 5, 0), r x F = (0*0 - 2*5, 2*0 - 0*0, 0) = (-10, 0, 0)
+        let sum = sum_thruster_forces(Vec3::ZERO, &[(Vec3::new(0., 0., 2.), Vec3::Y)], 5.);
+        assert_near(sum.force, Vec3::new(0., 5., 0.));
+        assert_near(sum.torque, Vec3::new(-10., 0., 0.));
+    }
+
+    #[test]
+    fn angled_thruster_splits_force() {
+        // 45 degrees between up and +x, r = (0, -1, 1) from com (1, 1, 0)
+        let direction = Vec3::new(1., 1., 0.).normalize();
+        let sum = sum_thruster_forces(Vec3::new(1., 1., 0.), &[(Vec3::new(1., 0., 1.), direction)], 4. * 2f32.sqrt());
+        // F = (4, 4, 0), r x F = (-1*0 - 1*4, 1*4 - 0*0, 0*4 - -1*4) = (-4, 4, 4)
+        assert_near(sum.force, Vec3::new(4., 4., 0.));
+        assert_near(sum.torque, Vec3::new(-4., 4., 4.));
+    }
+
+    #[test]
+    fn throttle_percent_is_clamped() {
+        assert_eq!(throttle_percent(THRUSTERS_FULL_POWER / 2.), 50.);
+        assert_eq!(throttle_percent(THRUSTERS_FULL_POWER * 2.), 100.);
+        assert_eq!(throttle_percent(-1.), 0.);
+    }
+}
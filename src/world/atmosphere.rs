@@ -0,0 +1,96 @@
+use crate::world::{CHUNK_LENGTH,TERRAIN_BASE_HEIGHT,TERRAIN_OFFSET_Y,OriginShiftEvent};
+
+use bevy::prelude::*;
+
+// isothermal atmosphere, density and pressure fall off exponentially above sea level
+#[derive(Clone, Debug, Resource)]
+pub struct Atmosphere {
+    // world height of sea level, moved along with the world origin
+    pub sea_level: f32,
+    // height over which density and pressure drop by a factor of e
+    pub scale_height: f32,
+    // air density at sea level, scaled down to suit block masses
+    pub sea_level_density: f32,
+    // air pressure at sea level, in kPa
+    pub sea_level_pressure: f32,
+}
+
+impl Default for Atmosphere {
+    fn default() -> Self {
+        Self {
+            sea_level: TERRAIN_OFFSET_Y + CHUNK_LENGTH * TERRAIN_BASE_HEIGHT,
+            scale_height: 400.,
+            sea_level_density: 0.05,
+            sea_level_pressure: 101.325,
+        }
+    }
+}
+
+impl Atmosphere {
+    // fraction of sea level density and pressure at world height, 1 at or below sea level
+    pub fn falloff(&self, y: f32) -> f32 {
+        if self.scale_height <= 0. {
+            return if y <= self.sea_level { 1. } else { 0. };
+        }
+        (-(y - self.sea_level).max(0.) / self.scale_height).exp()
+    }
+
+    pub fn density(&self, y: f32) -> f32 {
+        self.sea_level_density * self.falloff(y)
+    }
+
+    pub fn pressure(&self, y: f32) -> f32 {
+        self.sea_level_pressure * self.falloff(y)
+    }
+}
+
+// thrust interpolated between vacuum and sea level values by fraction of sea level pressure
+pub fn thrust_at_pressure(
+    sea_level_thrust: f32,
+    vacuum_thrust: f32,
+    pressure_fraction: f32,
+) -> f32 {
+    vacuum_thrust + (sea_level_thrust - vacuum_thrust) * pressure_fraction.clamp(0., 1.)
+}
+
+pub struct AtmospherePlugin;
+
+impl Plugin for AtmospherePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Atmosphere>();
+        app.add_systems(Update, shift_atmosphere);
+    }
+}
+
+fn shift_atmosphere(
+    mut atmosphere: ResMut<Atmosphere>,
+    mut shift_events: EventReader<OriginShiftEvent>,
+) {
+    for shift in shift_events.read() {
+        atmosphere.sea_level += shift.offset.y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sea_level_values() {
+        let atmosphere = Atmosphere::default();
+        assert_eq!(atmosphere.density(atmosphere.sea_level), atmosphere.sea_level_density);
+        assert_eq!(atmosphere.pressure(atmosphere.sea_level - 50.), atmosphere.sea_level_pressure);
+        assert_eq!(thrust_at_pressure(80., 100., atmosphere.falloff(atmosphere.sea_level)), 80.);
+    }
+
+    #[test]
+    fn one_scale_height_up_drops_by_e() {
+        let atmosphere = Atmosphere::default();
+        let y = atmosphere.sea_level + atmosphere.scale_height;
+        let e = std::f32::consts::E;
+        assert!((atmosphere.density(y) - 0.05 / e).abs() < 1E-6);
+        assert!((atmosphere.pressure(y) - 101.325 / e).abs() < 1E-3);
+        // 100 + (80 - 100) / e
+        assert!((thrust_at_pressure(80., 100., atmosphere.falloff(y)) - 92.642_41).abs() < 1E-3);
+    }
+}
@@ -13,39 +13,37 @@
 // limitations under the License.
 
 //! Isosurface definitions for use in multiple examples
+use crate::world::TerrainGenerator;
 use isosurface::{
     distance::Signed,
     math::Vec3,
     source::{HermiteSource, ScalarSource},
 };
+use std::sync::Arc;
 
 pub const CHUNK_LENGTH: f32 = 32.;
+// mean terrain height, in chunk lengths
+pub const TERRAIN_BASE_HEIGHT: f32 = 0.5;
 
+// samples generator within one chunk, in chunk-relative coordinates
 pub struct IsosurfaceSource {
     pub chunkx: i32,
     pub chunkz: i32,
     pub epsilon: f32,
+    pub generator: Arc<dyn TerrainGenerator>,
 }
 
 impl IsosurfaceSource {
-    pub fn new(chunkx: i32, chunkz: i32) -> Self {
+    pub fn new(generator: Arc<dyn TerrainGenerator>, chunkx: i32, chunkz: i32) -> Self {
         Self {
             chunkx, chunkz,
             epsilon: 0.000001,
+            generator,
         }
     }
 
-        
     pub fn heightfn(&self, x: f32, z: f32) -> f32 {
-        let x = (self.chunkx as f32) + x;
-        let z = (self.chunkz as f32) + z;
-        0.5 + 0.1 * (0.1 + x * 1.11).sin() + 
-            0.5 * ((x * 0.0911).sin() + (z * 0.0811).sin()) * 
-                (0.1 * (x * 10.011).sin() * (x * 1.0311).sin() + 
-                0.1 * (x * 9.3011).sin() * (x * 2.4311).sin() + 
-                0.1 * (z * 17.3011).sin() * (z * 1.9311).sin()) + 
-            0.1 * (0.1 + z * 1.31).sin() + 
-            0.1 * (z * 1.0311).sin()
+        self.generator.height((self.chunkx as f32) + x, (self.chunkz as f32) + z)
     }
 }
 
@@ -57,7 +55,7 @@ impl ScalarSource for IsosurfaceSource {
         // surface at the zero-crossing.
         // 
         // self.source.sample_scalar(q)
-        Signed(self.heightfn(p.x, p.z) - p.y)//TODO
+        Signed(self.generator.density((self.chunkx as f32) + p.x, p.y, (self.chunkz as f32) + p.z))
     }
 }
 
@@ -1,9 +1,13 @@
+mod atmosphere;
+pub use atmosphere::*;
 mod isosurface_source;
 pub use isosurface_source::*;
 mod lights_state;
 pub use lights_state::*;
 mod sounds_state;
 pub use sounds_state::*;
+mod terrain_generator;
+pub use terrain_generator::*;
 mod terrain_material;
 pub use terrain_material::*;
 mod water_state;
@@ -12,6 +16,8 @@ mod world_asset_loader;
 pub use world_asset_loader::*;
 mod world_loading;
 pub use world_loading::*;
+mod world_origin;
+pub use world_origin::*;
 mod world_state;
 pub use world_state::*;
 mod world_terrain;
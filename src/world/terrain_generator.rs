@@ -0,0 +1,194 @@
+use crate::world::TERRAIN_BASE_HEIGHT;
+
+use bevy::prelude::*;
+use serde::{Deserialize,Serialize};
+use std::sync::Arc;
+
+// terrain shape, positions and heights in chunk lengths from the absolute world origin
+pub trait TerrainGenerator: Send + Sync {
+    // height of terrain surface
+    fn height(&self, x: f32, z: f32) -> f32;
+
+    // positive inside the ground, zero at its surface, override for caves and overhangs
+    fn density(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.height(x, z) - y
+    }
+}
+
+// hashes lattice point with seed to 0..1
+fn lattice_value(seed: u64, x: i32, y: i32, z: i32) -> f32 {
+    let mut h = seed ^ 0x9E37_79B9_7F4A_7C15;
+    for v in [x, y, z] {
+        h = (h ^ (v as u32 as u64)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        h ^= h >> 31;
+    }
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+// smoothly interpolated lattice noise, 0..1
+pub fn value_noise(seed: u64, p: Vec3) -> f32 {
+    let base = p.floor();
+    let t = p - base;
+    let s = t * t * (Vec3::splat(3.) - 2. * t);
+    let (x, y, z) = (base.x as i32, base.y as i32, base.z as i32);
+    let corner = |dx: i32, dy: i32, dz: i32| lattice_value(seed, x + dx, y + dy, z + dz);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    lerp(
+        lerp(lerp(corner(0, 0, 0), corner(1, 0, 0), s.x), lerp(corner(0, 1, 0), corner(1, 1, 0), s.x), s.y),
+        lerp(lerp(corner(0, 0, 1), corner(1, 0, 1), s.x), lerp(corner(0, 1, 1), corner(1, 1, 1), s.x), s.y),
+        s.z)
+}
+
+// octaves of noise, each twice the frequency and half the amplitude of the previous, 0..1
+pub fn fractal_noise(seed: u64, p: Vec3, octaves: u32) -> f32 {
+    let (mut sum, mut amplitude, mut total) = (0., 1., 0.);
+    for octave in 0..octaves.max(1) {
+        sum += amplitude * value_noise(seed.wrapping_add(octave as u64), p * (1 << octave) as f32);
+        total += amplitude;
+        amplitude *= 0.5;
+    }
+    sum / total
+}
+
+// the original rolling hills, a fixed sum of sines
+pub struct SinesTerrain;
+
+impl TerrainGenerator for SinesTerrain {
+    fn height(&self, x: f32, z: f32) -> f32 {
+        TERRAIN_BASE_HEIGHT + 0.1 * (0.1 + x * 1.11).sin() +
+            0.5 * ((x * 0.0911).sin() + (z * 0.0811).sin()) *
+                (0.1 * (x * 10.011).sin() * (x * 1.0311).sin() +
+                0.1 * (x * 9.3011).sin() * (x * 2.4311).sin() +
+                0.1 * (z * 17.3011).sin() * (z * 1.9311).sin()) +
+            0.1 * (0.1 + z * 1.31).sin() +
+            0.1 * (z * 1.0311).sin()
+    }
+}
+
+pub struct FlatTerrain {
+    pub height: f32,
+}
+
+impl TerrainGenerator for FlatTerrain {
+    fn height(&self, _x: f32, _z: f32) -> f32 {
+        self.height
+    }
+}
+
+// seeded fractal noise hills, optionally carved by caves
+pub struct FractalTerrain {
+    pub seed: u64,
+    // height range around the base height
+    pub amplitude: f32,
+    // lowest noise frequency, per chunk length
+    pub frequency: f32,
+    pub octaves: u32,
+    // fraction of the ground carved out by caves, none at 0
+    pub caves: f32,
+}
+
+impl FractalTerrain {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            amplitude: 0.4,
+            frequency: 0.25,
+            octaves: 5,
+            caves: 0.,
+        }
+    }
+}
+
+impl TerrainGenerator for FractalTerrain {
+    fn height(&self, x: f32, z: f32) -> f32 {
+        let noise = fractal_noise(self.seed, Vec3::new(x, 0., z) * self.frequency, self.octaves);
+        TERRAIN_BASE_HEIGHT + self.amplitude * (2. * noise - 1.)
+    }
+
+    fn density(&self, x: f32, y: f32, z: f32) -> f32 {
+        let ground = self.height(x, z) - y;
+        if self.caves <= 0. {
+            return ground;
+        }
+        // solid where both the ground and the cave noise are
+        let cave = fractal_noise(self.seed ^ 0xCA7E, Vec3::new(x, y, z) * 4. * self.frequency, 3) - self.caves;
+        ground.min(cave)
+    }
+}
+
+// seeded sharp ridges from folded noise, with high peaks and wide valleys
+pub struct RidgedTerrain {
+    pub seed: u64,
+    pub amplitude: f32,
+    pub frequency: f32,
+    pub octaves: u32,
+}
+
+impl RidgedTerrain {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            amplitude: 0.7,
+            frequency: 0.15,
+            octaves: 5,
+        }
+    }
+}
+
+impl TerrainGenerator for RidgedTerrain {
+    fn height(&self, x: f32, z: f32) -> f32 {
+        let p = Vec3::new(x, 0., z) * self.frequency;
+        let (mut sum, mut amplitude, mut total) = (0., 1., 0.);
+        for octave in 0..self.octaves.max(1) {
+            let noise = value_noise(self.seed.wrapping_add(octave as u64), p * (1 << octave) as f32);
+            sum += amplitude * (1. - (2. * noise - 1.).abs()).powi(2);
+            total += amplitude;
+            amplitude *= 0.5;
+        }
+        // kept within the unit height of isosurface chunks
+        TERRAIN_BASE_HEIGHT + self.amplitude * (sum / total - 0.4)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum TerrainKind {
+    #[default]
+    Sines,
+    Fractal,
+    FractalCaves,
+    Ridged,
+    Flat,
+}
+
+// generator and seed of a world
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct TerrainConfig {
+    pub kind: TerrainKind,
+    pub seed: u64,
+}
+
+impl TerrainConfig {
+    pub fn generator(&self) -> Arc<dyn TerrainGenerator> {
+        match self.kind {
+            TerrainKind::Sines => Arc::new(SinesTerrain),
+            TerrainKind::Fractal => Arc::new(FractalTerrain::new(self.seed)),
+            TerrainKind::FractalCaves => Arc::new(FractalTerrain { caves: 0.3, ..FractalTerrain::new(self.seed) }),
+            TerrainKind::Ridged => Arc::new(RidgedTerrain::new(self.seed)),
+            TerrainKind::Flat => Arc::new(FlatTerrain { height: TERRAIN_BASE_HEIGHT }),
+        }
+    }
+}
+
+// terrain of the active world, shared with chunk building tasks
+#[derive(Clone, Resource)]
+pub struct ActiveTerrain {
+    pub generator: Arc<dyn TerrainGenerator>,
+}
+
+impl Default for ActiveTerrain {
+    fn default() -> Self {
+        Self {
+            generator: TerrainConfig::default().generator(),
+        }
+    }
+}
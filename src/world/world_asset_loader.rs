@@ -9,9 +9,15 @@ use thiserror::Error;
 // use bevy_rapier3d::prelude::*;
 use serde::Deserialize;
 use ron;
+use crate::world::{TerrainConfig,TerrainKind};
 
 #[derive(Asset, TypePath, Deserialize)]
 pub struct WorldAsset {
+    // terrain generator and its seed, original hills if left out
+    #[serde(default)]
+    pub terrain: TerrainKind,
+    #[serde(default)]
+    pub seed: u64,
     pub colliders: Vec<WorldCollider>,
     pub interactables: Vec<WorldInteractable>,
     pub lights: Vec<WorldLight>,
@@ -19,6 +25,15 @@ pub struct WorldAsset {
     pub sounds: Vec<WorldSound>,
 }
 
+impl WorldAsset {
+    pub fn terrain_config(&self) -> TerrainConfig {
+        TerrainConfig {
+            kind: self.terrain,
+            seed: self.seed,
+        }
+    }
+}
+
 // represents data for convex colliders defined for a world
 #[derive(Debug, Deserialize)]
 pub struct WorldCollider {
@@ -125,3 +140,18 @@ impl Plugin for WorldAssetLoaderPlugin {
             .init_asset_loader::<WorldAssetLoader>();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_selects_terrain() {
+        let world01 = ron::de::from_str::<WorldAsset>(include_str!("../../assets/worlds/world01.world")).unwrap();
+        assert_eq!(world01.terrain_config(), TerrainConfig::default());
+
+        let seeded = ron::de::from_str::<WorldAsset>(
+            "(terrain: Ridged, seed: 42, colliders: [], interactables: [], lights: [], props: [], sounds: [])").unwrap();
+        assert_eq!(seeded.terrain_config(), TerrainConfig { kind: TerrainKind::Ridged, seed: 42 });
+    }
+}
@@ -0,0 +1,107 @@
+use crate::character::{CharacterFpsMotionConfig,MoverState};
+use crate::props::piloted_grid;
+use crate::building::GridBlock;
+use crate::game_state::GameState;
+use crate::world::CHUNK_LENGTH;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+// distance of player or piloted grid from local origin that shifts the origin to them
+pub const ORIGIN_SHIFT_DISTANCE: f32 = 512.;
+
+// offset of local coordinates, in which all transforms live, from absolute world coordinates
+#[derive(Clone, Debug, Default, Resource)]
+pub struct WorldOrigin {
+    // absolute chunk at local origin
+    pub chunk: IVec3,
+}
+
+impl WorldOrigin {
+    // absolute position of local origin
+    pub fn translation(&self) -> Vec3 {
+        self.chunk.as_vec3() * CHUNK_LENGTH
+    }
+
+    pub fn to_absolute(&self, local: Vec3) -> Vec3 {
+        local + self.translation()
+    }
+
+    // whole chunks to move the origin by to bring focus near it, none within shift distance
+    pub fn shift_chunks(focus: Vec3) -> Option<IVec3> {
+        if focus.abs().max_element() < ORIGIN_SHIFT_DISTANCE {
+            return None;
+        }
+        Some((focus / CHUNK_LENGTH).round().as_ivec3())
+    }
+}
+
+// sent after all transforms moved by offset when the origin shifted
+#[derive(Clone, Copy, Debug, Event)]
+pub struct OriginShiftEvent {
+    pub offset: Vec3,
+}
+
+/// Floating origin, keeping the player near zero where f32 positions are precise
+pub struct WorldOriginPlugin;
+
+impl Plugin for WorldOriginPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<WorldOrigin>()
+        .add_event::<OriginShiftEvent>();
+
+        app.add_systems(PostUpdate, update_world_origin
+            .before(PhysicsSet::SyncBackend)
+            .run_if(in_state(GameState::Running)));
+    }
+}
+
+// shifts all root transforms by whole chunks once the player or piloted grid strays far
+fn update_world_origin(
+    mut origin: ResMut<WorldOrigin>,
+    mut shift_events: EventWriter<OriginShiftEvent>,
+    mover_query: Query<(Entity, &MoverState), With<CharacterFpsMotionConfig>>,
+    parents: Query<&Parent>,
+    blocks_query: Query<&GridBlock>,
+    global_transforms: Query<&GlobalTransform>,
+    mut roots_query: Query<&mut Transform, (Without<Parent>, Without<Node>)>,
+) {
+    let Ok((mover_entity, mover_state)) = mover_query.get_single() else {
+        return;
+    };
+    let focus_entity = piloted_grid(mover_state, &parents, &blocks_query).unwrap_or(mover_entity);
+    let Ok(focus) = global_transforms.get(focus_entity) else {
+        return;
+    };
+    let Some(shift) = WorldOrigin::shift_chunks(focus.translation()) else {
+        return;
+    };
+
+    let offset = -shift.as_vec3() * CHUNK_LENGTH;
+    for mut transform in roots_query.iter_mut() {
+        transform.translation += offset;
+    }
+    origin.chunk += shift;
+    shift_events.send(OriginShiftEvent { offset });
+    info!("World origin shifted to chunk {}", origin.chunk);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifts_by_whole_chunks_past_shift_distance() {
+        assert_eq!(WorldOrigin::shift_chunks(Vec3::new(500., -100., 300.)), None);
+        let focus = Vec3::new(600., 10., -20.);
+        let shift = WorldOrigin::shift_chunks(focus).unwrap();
+        assert_eq!(shift, IVec3::new(19, 0, -1));
+        // focus ends within half a chunk of the new origin, at the same absolute position
+        let shifted = focus - shift.as_vec3() * CHUNK_LENGTH;
+        assert!(shifted.abs().max_element() <= CHUNK_LENGTH / 2.);
+        let origin = WorldOrigin { chunk: shift };
+        assert_eq!(origin.to_absolute(shifted), WorldOrigin::default().to_absolute(focus));
+        assert!(WorldOrigin::shift_chunks(Vec3::new(0., -ORIGIN_SHIFT_DISTANCE, 0.)).is_some());
+    }
+}
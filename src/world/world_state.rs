@@ -1,5 +1,5 @@
 use crate::world::{
-    LightsStatePlugin,SoundsStatePlugin,
+    AtmospherePlugin,LightsStatePlugin,SoundsStatePlugin,WorldOriginPlugin,
     WorldInteraction};
 use bevy::prelude::*;
 use std::collections::HashMap;
@@ -40,8 +40,10 @@ impl Plugin for WorldStatePlugin {
     fn build(&self, app: &mut App) {
         app
         .add_plugins((
+            AtmospherePlugin,
             LightsStatePlugin,
             SoundsStatePlugin,
+            WorldOriginPlugin,
         ));
     }
 }
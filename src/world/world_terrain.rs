@@ -1,7 +1,9 @@
 use crate::inputs::MouseCamera;
 use crate::character::{CharacterFpsMotionConfig,CHARACTER_GROUP}; 
 use crate::game_state::GameState;
-use crate::world::{CHUNK_LENGTH,IsosurfaceSource,TerrainMaterialPlugin,TerrainMaterialState};
+use crate::loading::WorldAssets;
+use crate::world::{ActiveTerrain,CHUNK_LENGTH,IsosurfaceSource,TerrainConfig,TerrainGenerator,
+    TerrainMaterialPlugin,TerrainMaterialState,WorldAsset,WorldOrigin,WorldState};
 
 use bevy::{
     ecs::system::CommandQueue,
@@ -16,15 +18,82 @@ use isosurface::{
     MarchingCubes,
 };
 use std::collections::{HashSet,HashMap};
+use std::sync::Arc;
 
 const CHUNK_SEGS: usize = 64;
 
+// world height of chunk mesh origins
+pub const TERRAIN_OFFSET_Y: f32 = -100.0;
+
 pub const WORLD_GROUP: Group = Group::GROUP_2;
 
 pub struct WorldTerrainPlugin;
 
+// detail of a terrain chunk mesh
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub enum ChunkLod {
+    // full resolution isosurface with collider
+    Iso,
+    // heightfield with segments per chunk edge, skirted to hide cracks to neighbours
+    Heightfield(usize),
+}
+
+// absolute chunk position and detail
 #[derive(Debug,Clone,PartialEq,Eq,Hash)]
-pub struct MeshCacheKey([i32;2],bool);
+pub struct MeshCacheKey([i32;2],ChunkLod);
+
+// rings of heightfield chunks around the center 2x2 isosurface chunks, each ring
+// twice as wide as the previous one with half the segments per chunk
+#[derive(Clone, Debug, Resource)]
+pub struct TerrainLodSettings {
+    // distance from center chunks to which terrain is shown, in meters
+    pub view_distance: f32,
+    // width of the first ring in chunks, which has full resolution
+    pub first_ring_width: i32,
+    // segments per chunk edge of the coarsest rings
+    pub min_segs: usize,
+}
+
+impl Default for TerrainLodSettings {
+    fn default() -> Self {
+        Self {
+            // 5 chunks past the center, 12x12 chunks with most of them at quarter resolution
+            view_distance: 160.,
+            first_ring_width: 1,
+            min_segs: 8,
+        }
+    }
+}
+
+impl TerrainLodSettings {
+    // chunks shown beyond the center chunks in each direction
+    pub fn view_chunks(&self) -> i32 {
+        (self.view_distance / CHUNK_LENGTH).ceil().max(0.) as i32
+    }
+
+    // detail of chunk at offset from the first center chunk
+    pub fn chunk_lod(&self, offset: [i32;2]) -> ChunkLod {
+        // rings out from the center 2x2 chunks at offsets 0 and 1
+        let ring_dist = |o: i32| if o < 0 { -o } else { (o - 1).max(0) };
+        let dist = ring_dist(offset[0]).max(ring_dist(offset[1]));
+        if dist == 0 {
+            return ChunkLod::Iso;
+        }
+
+        let min_segs = self.min_segs.clamp(1, CHUNK_SEGS);
+        let mut ring_end = 0;
+        let mut width = self.first_ring_width.max(1);
+        let mut segs = CHUNK_SEGS;
+        loop {
+            ring_end += width;
+            if dist <= ring_end || segs / 2 < min_segs {
+                return ChunkLod::Heightfield(segs);
+            }
+            width *= 2;
+            segs /= 2;
+        }
+    }
+}
 
 // system state
 #[derive(Default, Resource)]
@@ -51,14 +120,33 @@ pub struct TerrainFadeout {
 impl Plugin for WorldTerrainPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(WorldTerrainState::default());
+        app.init_resource::<TerrainLodSettings>();
+        app.init_resource::<ActiveTerrain>();
         app.add_plugins((TerrainMaterialPlugin::default(),));
-        app.add_systems(OnEnter(GameState::WorldLoading), setup_active_terrain);
+        app.add_systems(OnEnter(GameState::WorldLoading), (select_world_terrain, setup_active_terrain));
         app.add_systems(Update, update_active_terrain.run_if(in_state(GameState::Running)));
         app.add_systems(Update, handle_terrian_loaded_tasks.run_if(in_state(GameState::Running)));
         app.add_systems(Update, handle_terrain_fadeout.run_if(in_state(GameState::Running)));
     }
 }
 
+// terrain of the world definition, original hills without one
+fn select_world_terrain(
+    world_state: Res<WorldState>,
+    world_assets: Res<WorldAssets>,
+    worlds: Res<Assets<WorldAsset>>,
+    mut terrain: ResMut<ActiveTerrain>,
+) {
+    let config = world_assets.world(&world_state.active_world)
+        .and_then(|handle| worlds.get(handle))
+        .map_or_else(|| {
+            warn!("No world definition for {}", world_state.active_world);
+            TerrainConfig::default()
+        }, |world| world.terrain_config());
+    info!("World terrain: {:?}", config);
+    terrain.generator = config.generator();
+}
+
 fn setup_active_terrain(
     mut commands: Commands,
 ) {
@@ -94,31 +182,36 @@ fn setup_active_terrain(
 fn update_active_terrain(
     mut commands: Commands, 
     mut terrain_state: ResMut<WorldTerrainState>,
+    lod_settings: Res<TerrainLodSettings>,
+    origin: Res<WorldOrigin>,
+    terrain: Res<ActiveTerrain>,
     chunks_query: Query<(Entity, &TerrainChunk)>,
     mover_query: Query<(&Transform, &CharacterFpsMotionConfig), Without<MouseCamera>>,
 ) {
     let (mover_transform, _mover) = mover_query.single();
-    let relative_pos = [mover_transform.translation.x, mover_transform.translation.z];
+    let absolute_pos = origin.to_absolute(mover_transform.translation);
+    let relative_pos = [absolute_pos.x, absolute_pos.z];
 
-    // use chunk shifted by 0.5 as center of 4x4 chunk grid
+    // use chunk shifted by 0.5 as center of chunk rings
     let shifted_chunk_pos = [
         ((-0.5 * CHUNK_LENGTH + relative_pos[0]) / CHUNK_LENGTH).round() as i32,
         ((-0.5 * CHUNK_LENGTH + relative_pos[1]) / CHUNK_LENGTH).round() as i32,
     ];
 
     // check if changed
-    if Some(shifted_chunk_pos) == terrain_state.last_chunk_pos {
+    if Some(shifted_chunk_pos) == terrain_state.last_chunk_pos && !lod_settings.is_changed() {
         return;
     }
     terrain_state.last_chunk_pos = Some(shifted_chunk_pos);
 
-    // spawn meshes, only center 4x4 is non-lod mesh
+    // spawn meshes, only center 2x2 is isosurface mesh
     let mut keep_ents = HashSet::<Entity>::new();
-    for x in -1..3 {
-        for z in -1..3 {
+    let view_chunks = lod_settings.view_chunks();
+    for x in -view_chunks..(view_chunks + 2) {
+        for z in -view_chunks..(view_chunks + 2) {
             let npos = [shifted_chunk_pos[0] + x, shifted_chunk_pos[1] + z];
-            let lod_chunk = !(x >= 0 && x < 2 && z >= 0 && z < 2);
-            keep_ents.insert(spawn_chunk(npos, lod_chunk, &mut commands, &mut terrain_state));
+            let lod = lod_settings.chunk_lod([x, z]);
+            keep_ents.insert(spawn_chunk(npos, lod, &terrain.generator, &mut commands, &mut terrain_state));
         }
     }
 
@@ -138,6 +231,7 @@ struct ComputeTransform(Task<CommandQueue>);
 /// generates mesh data in async task
 fn spawn_chunk_deferred(
     ckey: MeshCacheKey,
+    generator: Arc<dyn TerrainGenerator>,
     commands: &mut Commands, 
 ) -> Entity {
     let thread_pool = AsyncComputeTaskPool::get();
@@ -148,11 +242,12 @@ fn spawn_chunk_deferred(
 
     let task = thread_pool.spawn(async move {
         // compute here
-        let (mesh, collider_opt): (Mesh, Option<Collider>) = if ckey.1 {
-            (build_terrain_chunk_plane(ckey.0[0], ckey.0[1],), None)
-        } else {
-            let c = build_terrain_chunk_iso(ckey.0[0], ckey.0[1],);
-            (c.0, Some(c.1))
+        let (mesh, collider_opt): (Mesh, Option<Collider>) = match ckey.1 {
+            ChunkLod::Heightfield(segs) => (build_terrain_chunk_heightfield(generator, ckey.0[0], ckey.0[1], segs), None),
+            ChunkLod::Iso => {
+                let c = build_terrain_chunk_iso(generator, ckey.0[0], ckey.0[1],);
+                (c.0, Some(c.1))
+            }
         };
 
         // we use a raw command queue to pass a FnOne(&mut World) back to be
@@ -162,6 +257,8 @@ fn spawn_chunk_deferred(
             let terrain_material = {
                 world.resource::<TerrainMaterialState>().terrain_material.clone()
             };
+            // placed relative to the origin at time of completion
+            let origin = world.resource::<WorldOrigin>().translation();
             let mesh_handle = {
                 let mut meshes = world.resource_mut::<Assets<Mesh>>();
                 meshes.add(mesh)
@@ -175,9 +272,9 @@ fn spawn_chunk_deferred(
                     mesh: mesh_handle,
                     material: terrain_material.clone(),
                     transform: Transform::from_xyz(
-                        (ckey.0[0] as f32) * CHUNK_LENGTH - CHUNK_LENGTH / 2.0, 
-                        -100.0, 
-                        (ckey.0[1] as f32) * CHUNK_LENGTH - CHUNK_LENGTH / 2.0,),
+                        (ckey.0[0] as f32) * CHUNK_LENGTH - CHUNK_LENGTH / 2.0 - origin.x, 
+                        TERRAIN_OFFSET_Y - origin.y, 
+                        (ckey.0[1] as f32) * CHUNK_LENGTH - CHUNK_LENGTH / 2.0 - origin.z,),
                     ..default()
                 })
                 .insert(CollisionGroups::new(CHARACTER_GROUP | WORLD_GROUP, CHARACTER_GROUP | WORLD_GROUP));
@@ -227,25 +324,32 @@ fn handle_terrain_fadeout(
 
 fn spawn_chunk(
     chunk_pos: [i32; 2],
-    lod_chunk: bool,
+    lod: ChunkLod,
+    generator: &Arc<dyn TerrainGenerator>,
     commands: &mut Commands, 
     terrain_state: &mut ResMut<WorldTerrainState>,
 ) -> Entity {
     // current chunk
-    let ckey = MeshCacheKey(chunk_pos,lod_chunk);
+    let ckey = MeshCacheKey(chunk_pos,lod);
     if !terrain_state.ent_cache.contains_key(&ckey) {
-        let ent = spawn_chunk_deferred(ckey.clone(), commands);
+        let ent = spawn_chunk_deferred(ckey.clone(), generator.clone(), commands);
         terrain_state.ent_cache.insert(ckey.clone(), ent);
     }
     return terrain_state.ent_cache.get(&ckey).unwrap().clone();
 }
 
-fn build_terrain_chunk_iso(
+// height of terrain surface at absolute world position, chunks are offset by half their length
+pub fn terrain_height(generator: &dyn TerrainGenerator, x: f32, z: f32) -> f32 {
+    TERRAIN_OFFSET_Y + CHUNK_LENGTH * generator.height(x / CHUNK_LENGTH + 0.5, z / CHUNK_LENGTH + 0.5)
+}
+
+pub fn build_terrain_chunk_iso(
+    generator: Arc<dyn TerrainGenerator>,
     chunkx: i32,
     chunkz: i32,
 ) -> (Mesh,Collider) {
 
-    let iso = IsosurfaceSource::new(chunkx, chunkz);
+    let iso = IsosurfaceSource::new(generator, chunkx, chunkz);
     let sampler = Sampler::new(&iso);
 
     let mut mixed_vns = vec![];
@@ -296,52 +400,147 @@ fn build_terrain_chunk_iso(
     )
 }
 
-fn build_terrain_chunk_plane(
+// heightfield mesh with segs segments per chunk edge, and skirts hanging down
+// from its edges so no cracks show next to chunks of other detail
+pub fn build_terrain_chunk_heightfield(
+    generator: Arc<dyn TerrainGenerator>,
     chunkx: i32,
     chunkz: i32,
+    segs: usize,
 ) -> Mesh {
-    let segx = (chunkx as f32 * CHUNK_LENGTH) as f32;
-    let segz = (chunkz as f32 * CHUNK_LENGTH) as f32;
     let mut vertices: Vec<Vec3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
     let mut indices: Vec<[u32; 3]> = Vec::new();
-    let iso = IsosurfaceSource::new(chunkx, chunkz);
-
-    for ix in 0..=CHUNK_SEGS {
-        for iz in 0..=CHUNK_SEGS {
-            let seglen = CHUNK_LENGTH / (CHUNK_SEGS as f32);
-            let y = CHUNK_LENGTH * iso.heightfn(ix as f32 / (CHUNK_SEGS as f32), iz as f32 / (CHUNK_SEGS as f32));
-            vertices.push(Vec3::new(ix as f32 * seglen, y, iz as f32 * seglen));
+    let iso = IsosurfaceSource::new(generator, chunkx, chunkz);
+    let seglen = CHUNK_LENGTH / (segs as f32);
+
+    for ix in 0..=segs {
+        for iz in 0..=segs {
+            let (u, v) = (ix as f32 / (segs as f32), iz as f32 / (segs as f32));
+            vertices.push(Vec3::new(ix as f32 * seglen, CHUNK_LENGTH * iso.heightfn(u, v), iz as f32 * seglen));
+            // estimate normal from nearby slope
+            let e = 0.01;
+            normals.push(Vec3::new(
+                CHUNK_LENGTH * (iso.heightfn(u - e, v) - iso.heightfn(u + e, v)),
+                2. * e * CHUNK_LENGTH,
+                CHUNK_LENGTH * (iso.heightfn(u, v - e) - iso.heightfn(u, v + e))).normalize());
         }
     }
-    for ix in 0..CHUNK_SEGS {
-        for iz in 0..CHUNK_SEGS {
+    for ix in 0..segs {
+        for iz in 0..segs {
             // Start of the two relevant rows of vertices.
-            let row0 = ix * (CHUNK_SEGS + 1);
-            let row1 = (ix + 1) * (CHUNK_SEGS + 1);
+            let row0 = ix * (segs + 1);
+            let row1 = (ix + 1) * (segs + 1);
             // Two triangles making up a not-very-flat quad for each segment of the bowl.
             indices.push([(row0 + iz) as u32, (row0 + iz + 1) as u32, (row1 + iz) as u32]);
             indices.push([(row1 + iz) as u32, (row0 + iz + 1) as u32, (row1 + iz + 1) as u32]);
         }
     }
 
+    // skirts, deeper than the height error of coarse segments
+    let skirt_depth = 2. * seglen;
+    let index = |ix: usize, iz: usize| ix * (segs + 1) + iz;
+    let edges: [(Vec<usize>, Vec3); 4] = [
+        ((0..=segs).map(|i| index(0, i)).collect(), Vec3::NEG_X),
+        ((0..=segs).map(|i| index(segs, i)).collect(), Vec3::X),
+        ((0..=segs).map(|i| index(i, 0)).collect(), Vec3::NEG_Z),
+        ((0..=segs).map(|i| index(i, segs)).collect(), Vec3::Z),
+    ];
+    for (edge, outward) in edges.iter() {
+        for pair in edge.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let lowered = vertices.len();
+            for top in [a, b] {
+                vertices.push(vertices[top] - Vec3::Y * skirt_depth);
+                normals.push(normals[top]);
+            }
+            let (a_low, b_low) = (lowered, lowered + 1);
+            // face outwards, away from the chunk
+            let facing = (vertices[b] - vertices[a]).cross(vertices[a_low] - vertices[a]);
+            if facing.dot(*outward) > 0. {
+                indices.push([a as u32, b as u32, a_low as u32]);
+                indices.push([b as u32, b_low as u32, a_low as u32]);
+            } else {
+                indices.push([a as u32, a_low as u32, b as u32]);
+                indices.push([b as u32, a_low as u32, b_low as u32]);
+            }
+        }
+    }
+
     Mesh::new(PrimitiveTopology::TriangleList)
         .with_inserted_attribute(
-            Mesh::ATTRIBUTE_POSITION,
-            vertices.clone(),
+            Mesh::ATTRIBUTE_UV_0,
+            vertices.iter().map(|v| [v.x/2., v.z/2.]).collect::<Vec<[f32;2]>>(),
         )
         .with_inserted_attribute(
-            Mesh::ATTRIBUTE_UV_0,
-            vertices.clone().into_iter().map(|v| [v.x/2., v.z/2.]).collect::<Vec<[f32;2]>>(),
+            Mesh::ATTRIBUTE_POSITION,
+            vertices,
         )
         .with_inserted_attribute(
             Mesh::ATTRIBUTE_NORMAL,
-            vertices.clone().into_iter().map({|v|
-                // estimate normal from nearby slope
-                Vec3::new(iso.heightfn(v.x + segx - 0.1, v.z + segz) - 
-                    iso.heightfn(v.x + segx + 0.1, v.z + segz),1.0,
-                    iso.heightfn(v.x + segx, v.z + segz - 0.1) - 
-                    iso.heightfn(v.x + segx, v.z + segz + 0.1)).normalize().into()
-            }).collect::<Vec<[f32;3]>>(),
+            normals,
         )
-        .with_indices(Some(Indices::U32(indices.clone().into_iter().flatten().collect())))
+        .with_indices(Some(Indices::U32(indices.into_iter().flatten().collect())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::TerrainKind;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    fn chunk_heights(kind: TerrainKind, seed: u64, chunkx: i32, chunkz: i32) -> Vec<f32> {
+        let mesh = build_terrain_chunk_heightfield(TerrainConfig { kind, seed }.generator(), chunkx, chunkz, 4);
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            panic!("chunk mesh without positions");
+        };
+        positions.iter().map(|position| position[1]).collect()
+    }
+
+    #[test]
+    fn lod_rings_double_in_width() {
+        let settings = TerrainLodSettings { view_distance: 320., first_ring_width: 1, min_segs: 8 };
+        // center 2x2 chunks are at offsets 0 and 1
+        for offset in [[0, 0], [1, 1], [0, 1]] {
+            assert_eq!(settings.chunk_lod(offset), ChunkLod::Iso);
+        }
+        for (dist, segs) in [(1, 64), (2, 32), (3, 32), (4, 16), (7, 16), (8, 8), (10, 8)] {
+            // same ring on either side of the center
+            assert_eq!(settings.chunk_lod([1 + dist, 0]), ChunkLod::Heightfield(segs), "ring at {dist}");
+            assert_eq!(settings.chunk_lod([0, -dist]), ChunkLod::Heightfield(segs), "ring at {dist}");
+            assert_eq!(settings.chunk_lod([-dist, 1 + dist]), ChunkLod::Heightfield(segs), "ring at {dist}");
+        }
+    }
+
+    #[test]
+    fn view_distance_rounds_up_to_chunks() {
+        let with_distance = |view_distance| TerrainLodSettings { view_distance, ..default() }.view_chunks();
+        assert_eq!(TerrainLodSettings::default().view_chunks(), 5);
+        assert_eq!(with_distance(CHUNK_LENGTH + 1.), 2);
+        assert_eq!(with_distance(0.), 0);
+        assert_eq!(with_distance(-10.), 0);
+    }
+
+    #[test]
+    fn chunks_repeat_per_seed() {
+        for kind in [TerrainKind::Fractal, TerrainKind::FractalCaves, TerrainKind::Ridged] {
+            assert_eq!(chunk_heights(kind, 7, 2, -3), chunk_heights(kind, 7, 2, -3));
+            assert_ne!(chunk_heights(kind, 7, 2, -3), chunk_heights(kind, 8, 2, -3));
+        }
+    }
+
+    // heights of a few vertices of chunk (2, -3), catching changes to the generators
+    #[test]
+    fn chunk_snapshot() {
+        for (kind, seed, expected) in [
+            (TerrainKind::Fractal, 7, [16.58332, 16.130077, 15.787542, 15.32447]),
+            (TerrainKind::Fractal, 8, [20.8574, 20.544123, 19.65421, 19.082752]),
+            (TerrainKind::Ridged, 7, [17.023024, 18.96827, 18.966257, 20.774485]),
+        ] {
+            let heights = chunk_heights(kind, seed, 2, -3);
+            for (index, expected) in [0, 6, 12, 24].into_iter().zip(expected) {
+                assert!((heights[index] - expected).abs() < 1E-3, "{:?} seed {} vertex {}: {}", kind, seed, index, heights[index]);
+            }
+        }
+    }
 }